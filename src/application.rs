@@ -23,6 +23,7 @@ use glib::g_warning;
 use gtk::prelude::*;
 use gtk::{gio, glib};
 
+use crate::preferences::DewPreferencesWindow;
 use crate::DewDuctWindow;
 
 mod imp {
@@ -118,11 +119,29 @@ impl DewDuctApplication {
         let about_action = gio::ActionEntry::builder("about")
             .activate(move |app: &Self, _, _| app.show_about())
             .build();
-        self.add_action_entries([quit_action, about_action]);
+        let preferences_action = gio::ActionEntry::builder("preferences")
+            .activate(move |app: &Self, _, _| app.show_preferences())
+            .build();
+        self.add_action_entries([
+            quit_action,
+            about_action,
+            preferences_action,
+        ]);
+        self.set_accels_for_action("app.preferences", &["<primary>comma"]);
         self.set_accels_for_action("win.back", &["Escape"]);
         self.set_accels_for_action("win.search_started", &["<Ctrl>f"]);
     }
 
+    fn show_preferences(&self) {
+        let Some(win) =
+            self.active_window().and_downcast::<DewDuctWindow>()
+        else {
+            g_warning!("DewApplication", "no window to show preferences");
+            return;
+        };
+        DewPreferencesWindow::new(&win).present();
+    }
+
     fn show_about(&self) {
         let about = adw::AboutWindow::from_appdata(
             "/null/daknig/DewDuct/null.daknig.dewduct.metainfo.xml",
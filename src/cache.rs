@@ -1,6 +1,7 @@
 use std::fs::{self, metadata};
 use std::future::Future;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use glib::{g_debug, g_warning};
 use gtk::glib;
@@ -36,7 +37,6 @@ impl DewCache {
         fname: &Path,
         url: &str,
     ) -> impl std::future::Future<Output = anyhow::Result<Vec<u8>>> {
-        use anyhow::Context;
         use isahc::AsyncReadResponseExt;
 
         let fname = fname.to_owned();
@@ -56,30 +56,57 @@ impl DewCache {
                         .unwrap(),
                 })?;
             }
-            g_warning!(
-                "DewThumbnail",
-                "writing {} bytes to {}",
-                contents.len(),
-                fname.display()
-            );
-
-            // if possible, write to cache
-            if let Some(parent) = fname.parent() {
-                // try your best, if can't, then no worries
-                let _ = fs::create_dir_all(parent);
-            }
-
-            fs::write(&fname, &contents)
-                .with_context(|| {
-                    format!("error writing to {}", fname.display())
-                })
-                .unwrap_or_else(|e| {
-                    g_warning!("DewThumbnail", "{}", e);
-                });
+            DewCache::store(&fname, &contents);
             // now it is time to load that jpg into the thumbnail
             anyhow::Ok(contents)
         }
     }
+    /// Writes the file to the cache, if possible.
+    pub(crate) fn store(fname: &Path, contents: &[u8]) {
+        g_warning!(
+            "DewCache",
+            "writing {} bytes to {}",
+            contents.len(),
+            fname.display()
+        );
+
+        if let Some(parent) = fname.parent() {
+            // try your best, if can't, then no worries
+            let _ = fs::create_dir_all(parent);
+        }
+
+        fs::write(fname, contents)
+            .with_context(|| {
+                format!("error writing to {}", fname.display())
+            })
+            .unwrap_or_else(|e| {
+                g_warning!("DewCache", "{}", e);
+            });
+    }
+    /// Same as `fetch_file`, but a cached file older than `max_age` is
+    /// fetched again.
+    pub(crate) async fn fetch_file_max_age<Fetcher, Err, Fut>(
+        cache: &Self,
+        fname: PathBuf,
+        max_age: Duration,
+        fetcher: Fetcher,
+    ) -> Result<Vec<u8>, Err>
+    where
+        Fetcher: Fn(&Path) -> Fut,
+        Fut: Future<Output = Result<Vec<u8>, Err>>,
+    {
+        let path = cache.dir().join(&fname);
+        let is_stale = metadata(&path)
+            .and_then(|m| m.modified())
+            .is_ok_and(|modified| {
+                modified.elapsed().map_or(true, |age| age > max_age)
+            });
+        if is_stale {
+            g_debug!("DewCache", "{} is stale", path.display());
+            let _ = fs::remove_file(&path);
+        }
+        Self::fetch_file(cache, fname, fetcher).await
+    }
     /// cache: the cache with the directory where the info should be stored.
     /// fname: file we are looking for, relative to the cache.
     /// fetcher: function for fetching said file, if it is not in cache.
@@ -30,6 +30,8 @@ use gtk::{prelude::*, subclass::prelude::*};
 use anyhow::Context;
 
 use crate::cache::DewCache;
+use crate::channel_page::DewChannelPage;
+use crate::player_bar::{BarMode, Track};
 use crate::util::{cache, cache_dir};
 use crate::yt_item_list::DewYtItem;
 
@@ -91,28 +93,23 @@ mod imp {
             }
         }
         #[template_callback]
-        fn background_clicked(&self) {
-            g_warning!(
-                "DewChannelHeader",
-                "background {} clicked!",
-                self.id.borrow()
-            );
+        async fn background_clicked(&self) {
+            self.win().play_audio(self.tracks()).await;
         }
         #[template_callback]
-        fn play_all_clicked(&self) {
-            g_warning!(
-                "DewChannelHeader",
-                "play_all {} clicked!",
-                self.id.borrow()
-            );
+        async fn play_all_clicked(&self) {
+            let win = self.win();
+            let videos = self.videos();
+            if videos.is_empty() {
+                win.toast("Nothing to play yet");
+                return;
+            }
+            win.enqueue(&videos);
+            win.start_queue(BarMode::Video).await;
         }
         #[template_callback]
-        fn poppup_clicked(&self) {
-            g_warning!(
-                "DewChannelHeader",
-                "poppup {} clicked!",
-                self.id.borrow()
-            );
+        async fn poppup_clicked(&self) {
+            self.win().play_popup(self.tracks()).await;
         }
     }
 
@@ -120,6 +117,17 @@ mod imp {
         fn win(&self) -> crate::window::DewDuctWindow {
             self.obj().root().and_downcast().unwrap()
         }
+        /// The videos of the channel page this header is on.
+        fn videos(&self) -> Vec<DewYtItem> {
+            self.obj()
+                .ancestor(DewChannelPage::static_type())
+                .and_downcast::<DewChannelPage>()
+                .map(|page| page.videos())
+                .unwrap_or_default()
+        }
+        fn tracks(&self) -> Vec<Track> {
+            self.videos().iter().map(Track::from).collect()
+        }
         fn is_subbed(&self, list_store: &gio::ListStore) -> bool {
             list_store.into_iter().flatten().any(|item| {
                 item.downcast_ref::<DewYtItem>().is_some_and(|item| {
@@ -19,6 +19,7 @@
  */
 
 use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 
 #[allow(unused_imports)]
 use adw::{prelude::*, subclass::prelude::*};
@@ -27,11 +28,18 @@ use gtk::{gio, glib};
 #[allow(unused_imports)]
 use gtk::{prelude::*, subclass::prelude::*};
 
-use invidious::{channel::Channel, ClientAsyncTrait};
+use invidious::{
+    channel::{Channel, ChannelVideos},
+    hidden::Comment,
+    ClientAsync, ClientAsyncTrait, CommonPlaylist, CommonVideo,
+    InvidiousError, PublicItems,
+};
 
 use crate::{
+    comment_row::DewCommentRow,
+    util,
     window::DewDuctWindow,
-    yt_item_list::{DewYtItem, DewYtItemList},
+    yt_item_list::{DewYtItem, DewYtItemKind, DewYtItemList},
 };
 
 mod imp {
@@ -42,9 +50,36 @@ mod imp {
     pub struct DewChannelPage {
         // Template widgets
         #[template_child]
+        pub(super) tab_stack: TemplateChild<adw::ViewStack>,
+        #[template_child]
         pub(super) vid_list: TemplateChild<DewYtItemList>,
+        #[template_child]
+        pub(super) shorts_list: TemplateChild<DewYtItemList>,
+        #[template_child]
+        pub(super) streams_list: TemplateChild<DewYtItemList>,
+        #[template_child]
+        pub(super) playlists_list: TemplateChild<DewYtItemList>,
+        #[template_child]
+        pub(super) community_list: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub(super) about_description: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub(super) about_subscribers: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub(super) about_views: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub(super) about_joined: TemplateChild<gtk::Label>,
+
+        #[template_child]
+        pub(super) community_scroll: TemplateChild<gtk::ScrolledWindow>,
 
         pub(super) channel: RefCell<Option<Channel>>,
+        /// The tabs that were already loaded for this channel.
+        pub(super) loaded_tabs: RefCell<HashSet<String>>,
+        /// Of the tabs that have more to load.
+        pub(super) continuations: RefCell<HashMap<String, String>>,
+        /// The `<channel id>/<tab>` pages being fetched right now.
+        pub(super) loading: RefCell<HashSet<String>>,
     }
 
     #[glib::object_subclass]
@@ -62,32 +97,313 @@ mod imp {
         }
     }
 
-    impl ObjectImpl for DewChannelPage {}
+    impl ObjectImpl for DewChannelPage {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            self.tab_stack.connect_visible_child_name_notify(
+                glib::clone!(@weak self as page => move |_| {
+                    glib::spawn_future_local(async move {
+                        page.load_visible_tab().await;
+                    });
+                }),
+            );
+
+            // the next page, once the user scrolls to the end.
+            for (tab, list) in [
+                ("videos", &*self.vid_list),
+                ("shorts", &*self.shorts_list),
+                ("streams", &*self.streams_list),
+                ("playlists", &*self.playlists_list),
+            ] {
+                list.connect_end_reached(
+                    glib::clone!(@weak self as page => move |_| {
+                        glib::spawn_future_local(async move {
+                            page.load_tab(tab, true).await;
+                        });
+                    }),
+                );
+            }
+            self.community_scroll.connect_edge_reached(
+                glib::clone!(@weak self as page => move |_, position| {
+                    if position != gtk::PositionType::Bottom {
+                        return;
+                    }
+                    glib::spawn_future_local(async move {
+                        page.load_tab("community", true).await;
+                    });
+                }),
+            );
+        }
+    }
     impl WidgetImpl for DewChannelPage {}
     impl BoxImpl for DewChannelPage {}
 
     // #[gtk::template_callbacks]
     impl DewChannelPage {
         pub fn set_channel(&self, channel: Channel) {
-            let header = DewYtItem::header(&channel);
+            // the videos come with the tab, the header is there already.
+            self.vid_list
+                .set_from_vec(vec![DewYtItem::header(&channel)]);
+            self.set_about(&channel);
+            g_warning!("DewChannelPage", "changed to id {}", &channel.id);
+            self.channel.replace(Some(channel));
+
+            self.loaded_tabs.borrow_mut().clear();
+            self.continuations.borrow_mut().clear();
+            for list in [
+                &*self.shorts_list,
+                &*self.streams_list,
+                &*self.playlists_list,
+            ] {
+                list.set_from_vec(vec![]);
+            }
+            while let Some(child) = self.community_list.first_child() {
+                self.community_list.remove(&child);
+            }
+            self.tab_stack.set_visible_child_name("videos");
+        }
+
+        fn set_about(&self, channel: &Channel) {
+            self.about_description.set_text(&channel.description);
+            self.about_description
+                .set_visible(!channel.description.is_empty());
+            self.about_subscribers.set_text(
+                &(util::format_semi_engineering(
+                    channel.subscribers as f32,
+                ) + " subscribers"),
+            );
+            self.about_views.set_text(
+                &(util::format_semi_engineering(
+                    channel.total_views as f32,
+                ) + " views"),
+            );
+            let joined =
+                glib::DateTime::from_unix_local(channel.joined as i64)
+                    .and_then(|joined| joined.format("%x"));
+            self.about_joined.set_text(
+                &joined.map_or(String::new(), |joined| {
+                    format!("Joined {joined}")
+                }),
+            );
+        }
+
+        fn channel_id(&self) -> Option<String> {
+            self.channel
+                .borrow()
+                .as_ref()
+                .map(|channel| channel.id.clone())
+        }
+
+        /// Fetches the visible tab, the first time it is shown for this
+        /// channel.
+        pub(super) async fn load_visible_tab(&self) {
+            let Some(tab) = self.tab_stack.visible_child_name() else {
+                return;
+            };
+            if tab != "about" {
+                self.load_tab(&tab, false).await;
+            }
+        }
+
+        /// The first page of the tab, unless it was loaded already, or
+        /// with `more` the next one, if there is one.
+        pub(super) async fn load_tab(&self, tab: &str, more: bool) {
+            let Some(id) = self.channel_id() else {
+                return;
+            };
+            let continuation = match more {
+                true => match self.continuations.borrow().get(tab) {
+                    Some(continuation) => Some(continuation.clone()),
+                    None => return,
+                },
+                false => None,
+            };
+            // one page at a time.
+            if !self.loaded_tabs.borrow_mut().insert(tab.to_owned())
+                && !more
+            {
+                return;
+            }
+            let loading = format!("{id}/{tab}");
+            if !self.loading.borrow_mut().insert(loading.clone()) {
+                return;
+            }
+
+            let window: DewDuctWindow =
+                self.obj().root().and_downcast().unwrap();
+            let pool = window.instance_pool();
+            let fetch_id = id.clone();
+            let fetch_tab = tab.to_owned();
+            let items = window
+                .spawn(async move {
+                    pool.request(|invidious| {
+                        let id = fetch_id.clone();
+                        let tab = fetch_tab.clone();
+                        let continuation = continuation.clone();
+                        async move {
+                            fetch_tab_items(
+                                &invidious,
+                                &id,
+                                &tab,
+                                continuation.as_deref(),
+                            )
+                            .await
+                        }
+                    })
+                    .await
+                })
+                .await;
+            self.loading.borrow_mut().remove(&loading);
+
+            // another channel might have been opened meanwhile.
+            if self.channel_id().as_ref() != Some(&id) {
+                return;
+            }
+            let (items, continuation) = match items {
+                Ok(Ok(items)) => items,
+                Ok(Err(err)) => {
+                    g_warning!(
+                        "DewChannelPage",
+                        "can't load {} of {}: {:#}",
+                        tab,
+                        id,
+                        err
+                    );
+                    self.tab_failed(&window, tab, more);
+                    return;
+                }
+                Err(err) => {
+                    g_warning!("DewChannelPage", "{}", err);
+                    self.tab_failed(&window, tab, more);
+                    return;
+                }
+            };
+            match continuation {
+                Some(continuation) => self
+                    .continuations
+                    .borrow_mut()
+                    .insert(tab.to_owned(), continuation),
+                None => self.continuations.borrow_mut().remove(tab),
+            };
 
-            self.vid_list.set_from_vec(
-                Some(header)
-                    .into_iter()
-                    .chain(
+            match items {
+                TabItems::Videos(videos) => {
+                    let list = match tab {
+                        "videos" => &self.vid_list,
+                        "shorts" => &self.shorts_list,
+                        _ => &self.streams_list,
+                    };
+                    list.append(
+                        videos
+                            .iter()
+                            .map(|x: &CommonVideo| x.into())
+                            .collect(),
+                    );
+                }
+                TabItems::Playlists(playlists) => {
+                    self.playlists_list.append(
+                        playlists
+                            .into_iter()
+                            .map(DewYtItem::from)
+                            .collect(),
+                    )
+                }
+                TabItems::Community(posts) => {
+                    for mut post in posts {
+                        // replies are fetched by video id, which posts
+                        // don't have.
+                        post.replies = None;
+                        let row = DewCommentRow::new();
+                        row.set_from_comment(&id, &post);
+                        self.community_list.append(&row);
+                    }
+                }
+            }
+        }
+
+        /// Lets the user know, and have it tried again.
+        fn tab_failed(
+            &self,
+            window: &DewDuctWindow,
+            tab: &str,
+            more: bool,
+        ) {
+            if !more {
+                self.loaded_tabs.borrow_mut().remove(tab);
+            }
+            // better the few that come with the channel than nothing.
+            if tab == "videos" && !more {
+                if let Some(channel) = self.channel.borrow().as_ref() {
+                    self.vid_list.append(
                         channel
                             .lastest_videos
                             .iter()
-                            .map(|x: &invidious::CommonVideo| x.into()),
-                    )
-                    .collect::<Vec<_>>(),
-            );
-            g_warning!("DewChannelPage", "changed to id {}", &channel.id);
-            self.channel.replace(Some(channel));
+                            .map(|x: &CommonVideo| x.into())
+                            .collect(),
+                    );
+                }
+            }
+            let what = match tab {
+                "streams" => "live streams",
+                "community" => "community posts",
+                tab => tab,
+            };
+            window.toast(&format!("Can't load the {what}"));
         }
     }
 }
 
+/// The contents of one of the lazily loaded tabs.
+enum TabItems {
+    Videos(Vec<CommonVideo>),
+    Playlists(Vec<CommonPlaylist>),
+    Community(Vec<Comment>),
+}
+
+/// A page of the tab, and the continuation for the next one.
+async fn fetch_tab_items(
+    invidious: &ClientAsync,
+    id: &str,
+    tab: &str,
+    continuation: Option<&str>,
+) -> Result<(TabItems, Option<String>), InvidiousError> {
+    let params = continuation
+        .map(|continuation| format!("continuation={continuation}"));
+    let params = params.as_deref();
+    Ok(match tab {
+        "videos" => {
+            let videos = invidious.channel_videos(id, params).await?;
+            (TabItems::Videos(videos.videos), videos.continuation)
+        }
+        "playlists" => {
+            let playlists = invidious.channel_playlists(id, params).await?;
+            (
+                TabItems::Playlists(playlists.playlists),
+                playlists.continuation,
+            )
+        }
+        "community" => {
+            let posts = invidious.channel_comments(id, params).await?;
+            (TabItems::Community(posts.comments), posts.continuation)
+        }
+        // the client has no methods for these.
+        _ => {
+            let url = match params {
+                Some(params) => {
+                    format!("api/v1/channels/{id}/{tab}?{params}")
+                }
+                None => format!("api/v1/channels/{id}/{tab}"),
+            };
+            let res = InvidiousError::as_fetch_error(
+                invidious.fetch(&url).await,
+            )?;
+            let videos = ChannelVideos::from_str(res)?;
+            (TabItems::Videos(videos.videos), videos.continuation)
+        }
+    })
+}
+
 glib::wrapper! {
     pub struct DewChannelPage(ObjectSubclass<imp::DewChannelPage>)
         @extends gtk::Widget, gtk::Box,
@@ -100,7 +416,39 @@ impl DewChannelPage {
     }
 
     pub fn set_channel(&self, channel: Channel) {
-        self.imp().set_channel(channel)
+        self.imp().set_channel(channel);
+        glib::spawn_future_local(
+            glib::clone!(@weak self as page => async move {
+                page.imp().load_visible_tab().await;
+            }),
+        );
+    }
+
+    /// The videos listed, without the header; until the videos tab loads,
+    /// the latest ones that come with the channel.
+    pub fn videos(&self) -> Vec<DewYtItem> {
+        let videos: Vec<DewYtItem> = self
+            .imp()
+            .vid_list
+            .get_vec()
+            .into_iter()
+            .filter(|item| item.kind() == DewYtItemKind::Video)
+            .collect();
+        if !videos.is_empty() {
+            return videos;
+        }
+        self.imp()
+            .channel
+            .borrow()
+            .as_ref()
+            .map(|channel| {
+                channel
+                    .lastest_videos
+                    .iter()
+                    .map(|x: &CommonVideo| x.into())
+                    .collect()
+            })
+            .unwrap_or_default()
     }
 
     fn window(&self) -> DewDuctWindow {
@@ -109,19 +457,19 @@ impl DewChannelPage {
 
     pub async fn set_channel_id(&self, id: &str) {
         let id = id.to_owned();
-        let invidious = self.async_invidious_client();
+        let pool = self.window().instance_pool();
         let Ok(channel) = self
             .window()
             .spawn(async move {
-                invidious.channel(&id, None).await.map_err(|err| {
-                    g_warning!(
-                        "DewChannelPage",
-                        "cant load {id}: {err:#?}"
-                    );
+                pool.request(|invidious| {
+                    let id = id.clone();
+                    async move { invidious.channel(&id, None).await }
+                })
+                .await
+                .map_err(|err| {
                     g_warning!(
                         "DewChannelPage",
-                        "the instance used was {}",
-                        invidious.instance
+                        "cant load channel: {err:#}"
                     );
                 })
             })
@@ -134,12 +482,6 @@ impl DewChannelPage {
 
         self.set_channel(channel);
     }
-
-    pub fn async_invidious_client(&self) -> invidious::ClientAsync {
-        let window: crate::window::DewDuctWindow =
-            self.root().and_downcast().unwrap();
-        window.async_invidious_client()
-    }
 }
 
 impl Default for DewChannelPage {
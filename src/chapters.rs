@@ -0,0 +1,213 @@
+/* chapters.rs
+ *
+ * Copyright 2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+//! Chapters, as they are listed in the description of many videos: a
+//! timestamp on every line, with the title of the chapter next to it.
+
+use crate::description::timestamps;
+
+/// Like YouTube, a list of chapters needs at least that many of them.
+const MIN_CHAPTERS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    /// In seconds.
+    pub start: u64,
+    pub title: String,
+}
+
+/// The chapters listed in a video description; empty if there are none.
+/// They start at `0:00`, and every one starts after the one before it;
+/// lines without timestamps in between are skipped.
+pub fn parse_chapters(description: &str) -> Vec<Chapter> {
+    let mut chapters: Vec<Chapter> = vec![];
+    for line in description.lines() {
+        let Some((start, end, seconds)) =
+            timestamps(line).into_iter().next()
+        else {
+            continue;
+        };
+        match chapters.last() {
+            None if seconds != 0 => continue,
+            Some(last) if seconds <= last.start => break,
+            _ => {}
+        }
+        chapters.push(Chapter {
+            start: seconds,
+            title: chapter_title(&line[..start], &line[end..]),
+        });
+    }
+    if chapters.len() < MIN_CHAPTERS {
+        chapters.clear();
+    }
+    chapters
+}
+
+/// What is left of a line without its timestamp, like `- Intro` or
+/// `1. (` and `) Intro`, without the punctuation around it.
+fn chapter_title(before: &str, after: &str) -> String {
+    let separators: &[char] =
+        &['-', '–', '—', ':', '|', '(', ')', '[', ']'];
+    let trim = |s: &str| {
+        s.trim_matches(|c: char| {
+            c.is_whitespace() || separators.contains(&c)
+        })
+        .to_owned()
+    };
+    let before = trim(before);
+    let after = trim(after);
+    // a list number before the timestamp is no title.
+    let numbered = before.strip_suffix('.').is_some_and(|n| {
+        !n.is_empty() && n.chars().all(|c| c.is_ascii_digit())
+    });
+    match (before.is_empty() || numbered, after.is_empty()) {
+        (true, _) => after,
+        (false, true) => before,
+        (false, false) => format!("{before} {after}"),
+    }
+}
+
+/// Like `1:23`, or `1:02:03` for long videos.
+pub fn format_timestamp(seconds: u64) -> String {
+    let (hrs, mins, secs) =
+        (seconds / 3600, (seconds / 60) % 60, seconds % 60);
+    match hrs {
+        0 => format!("{mins}:{secs:02}"),
+        hrs => format!("{hrs}:{mins:02}:{secs:02}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(start: u64, title: &str) -> Chapter {
+        Chapter {
+            start,
+            title: title.to_owned(),
+        }
+    }
+
+    #[test]
+    fn timestamp_first() {
+        let description = "Today we build a shed.\n\
+            \n\
+            0:00 Intro\n\
+            1:23 Foundations\n\
+            12:05 The roof\n\
+            \n\
+            Thanks for watching!";
+        assert_eq!(
+            parse_chapters(description),
+            [
+                chapter(0, "Intro"),
+                chapter(83, "Foundations"),
+                chapter(725, "The roof"),
+            ]
+        );
+    }
+
+    #[test]
+    fn separators_and_brackets() {
+        let description = "00:00 - Intro\n\
+            (02:30) Part one\n\
+            [05:00] | Part two\n\
+            07:45 – Outro";
+        assert_eq!(
+            parse_chapters(description),
+            [
+                chapter(0, "Intro"),
+                chapter(150, "Part one"),
+                chapter(300, "Part two"),
+                chapter(465, "Outro"),
+            ]
+        );
+    }
+
+    #[test]
+    fn title_first() {
+        let description = "Intro - 0:00\nMain course: 3:10\nDessert 9:59";
+        assert_eq!(
+            parse_chapters(description),
+            [
+                chapter(0, "Intro"),
+                chapter(190, "Main course"),
+                chapter(599, "Dessert"),
+            ]
+        );
+    }
+
+    #[test]
+    fn numbered_and_hours() {
+        let description = "1. 0:00:00 Start\n\
+            2. 0:45:10 Middle\n\
+            3. 1:02:03 End";
+        assert_eq!(
+            parse_chapters(description),
+            [
+                chapter(0, "Start"),
+                chapter(2710, "Middle"),
+                chapter(3723, "End"),
+            ]
+        );
+    }
+
+    #[test]
+    fn timestamps_in_text_before_the_chapters() {
+        let description = "The best part is at 4:20, trust me.\n\
+            0:00 Intro\n\
+            4:20 The best part\n\
+            6:00 Outro";
+        assert_eq!(
+            parse_chapters(description),
+            [
+                chapter(0, "Intro"),
+                chapter(260, "The best part"),
+                chapter(360, "Outro"),
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_when_out_of_order() {
+        let description = "0:00 Intro\n\
+            1:00 One\n\
+            2:00 Two\n\
+            1:30 See 1:30 for more";
+        assert_eq!(
+            parse_chapters(description),
+            [chapter(0, "Intro"), chapter(60, "One"), chapter(120, "Two")]
+        );
+    }
+
+    #[test]
+    fn too_few_or_not_from_the_start() {
+        assert!(parse_chapters("0:00 Intro\n5:00 Outro").is_empty());
+        assert!(parse_chapters("0:30 A\n1:00 B\n1:30 C").is_empty());
+        assert!(parse_chapters("No chapters here.").is_empty());
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(format_timestamp(0), "0:00");
+        assert_eq!(format_timestamp(83), "1:23");
+        assert_eq!(format_timestamp(3723), "1:02:03");
+    }
+}
@@ -0,0 +1,186 @@
+/* comment_row.rs
+ *
+ * Copyright 2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::{Cell, RefCell};
+
+#[allow(unused_imports)]
+use adw::{prelude::*, subclass::prelude::*};
+use glib::g_warning;
+use gtk::glib;
+#[allow(unused_imports)]
+use gtk::{prelude::*, subclass::prelude::*};
+
+use invidious::hidden::Comment;
+
+use crate::comments_view::{fetch_comments, reply_thread};
+use crate::util;
+use crate::window::DewDuctWindow;
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, gtk::CompositeTemplate)]
+    #[template(resource = "/null/daknig/DewDuct/comment_row.ui")]
+    pub struct DewCommentRow {
+        #[template_child]
+        pub(super) author: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub(super) published: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub(super) likes: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub(super) content: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub(super) replies_button: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub(super) replies_revealer: TemplateChild<gtk::Revealer>,
+        #[template_child]
+        pub(super) reply_list: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub(super) more_replies_button: TemplateChild<gtk::Button>,
+
+        /// Of the video the comment is on.
+        pub(super) video_id: RefCell<String>,
+        /// For the next page of replies.
+        pub(super) continuation: RefCell<Option<String>>,
+        /// The first page of replies was asked for already.
+        pub(super) replies_loaded: Cell<bool>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DewCommentRow {
+        const NAME: &'static str = "DewCommentRow";
+        type Type = super::DewCommentRow;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for DewCommentRow {}
+    impl WidgetImpl for DewCommentRow {}
+    impl BoxImpl for DewCommentRow {}
+
+    #[gtk::template_callbacks]
+    impl DewCommentRow {
+        #[template_callback]
+        async fn toggle_replies(&self) {
+            let shown = self.replies_button.is_active();
+            self.replies_revealer.set_reveal_child(shown);
+            if shown && !self.replies_loaded.replace(true) {
+                self.load_replies().await;
+            }
+        }
+        #[template_callback]
+        async fn load_replies(&self) {
+            let Some(continuation) = self.continuation.borrow().clone()
+            else {
+                return;
+            };
+            let window: DewDuctWindow =
+                self.obj().root().and_downcast().unwrap();
+            let id = self.video_id.borrow().clone();
+
+            self.more_replies_button.set_sensitive(false);
+            let replies =
+                fetch_comments(&window, id.clone(), Some(continuation))
+                    .await;
+            self.more_replies_button.set_sensitive(true);
+
+            let replies = match replies {
+                Ok(replies) => replies,
+                Err(err) => {
+                    g_warning!(
+                        "DewCommentRow",
+                        "can't load replies on {}: {:#}",
+                        id,
+                        err
+                    );
+                    window.toast("Can't load the replies");
+                    self.replies_loaded.set(false);
+                    return;
+                }
+            };
+            for reply in &replies.comments {
+                let row = super::DewCommentRow::new();
+                row.set_from_comment(&id, reply);
+                self.reply_list.append(&row);
+            }
+            self.more_replies_button
+                .set_visible(replies.continuation.is_some());
+            self.continuation.replace(replies.continuation);
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct DewCommentRow(ObjectSubclass<imp::DewCommentRow>)
+        @extends gtk::Widget, gtk::Box,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl DewCommentRow {
+    pub fn new() -> Self {
+        glib::Object::builder().build()
+    }
+
+    pub fn set_from_comment(&self, video_id: &str, comment: &Comment) {
+        let imp = self.imp();
+        imp.video_id.replace(video_id.to_owned());
+
+        imp.author.set_text(&comment.author);
+        if comment.channel_owner {
+            imp.author.add_css_class("accent");
+        }
+        let published = util::format_published(comment.published)
+            .unwrap_or_else(|_| comment.published_text.clone());
+        imp.published.set_text(&match comment.edited {
+            true => published + " (edited)",
+            false => published,
+        });
+        imp.likes.set_text(
+            &(util::format_semi_engineering(comment.likes as f32)
+                + " likes"),
+        );
+        imp.content.set_text(&comment.content);
+
+        let thread = comment.replies.as_ref().and_then(reply_thread);
+        if let Some((count, continuation)) = thread {
+            imp.replies_button.set_label(&match count {
+                1 => "1 reply".to_owned(),
+                n => format!("{n} replies"),
+            });
+            imp.replies_button.set_visible(true);
+            imp.continuation.replace(Some(continuation));
+        }
+    }
+}
+
+impl Default for DewCommentRow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,207 @@
+/* comments_view.rs
+ *
+ * Copyright 2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::{Cell, RefCell};
+
+#[allow(unused_imports)]
+use adw::{prelude::*, subclass::prelude::*};
+use glib::g_warning;
+use gtk::glib;
+#[allow(unused_imports)]
+use gtk::{prelude::*, subclass::prelude::*};
+
+use invidious::hidden::Replies;
+use invidious::video::Comments;
+use invidious::ClientAsyncTrait;
+
+use crate::comment_row::DewCommentRow;
+use crate::window::DewDuctWindow;
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, gtk::CompositeTemplate)]
+    #[template(resource = "/null/daknig/DewDuct/comments_view.ui")]
+    pub struct DewCommentsView {
+        #[template_child]
+        pub(super) stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub(super) spinner_page: TemplateChild<gtk::Spinner>,
+        #[template_child]
+        pub(super) error_page: TemplateChild<adw::StatusPage>,
+        #[template_child]
+        pub(super) comments_page: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub(super) comment_list: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub(super) more_button: TemplateChild<gtk::Button>,
+
+        /// Of the video the comments are for.
+        pub(super) video_id: RefCell<Option<String>>,
+        /// For the next page of comments.
+        pub(super) continuation: RefCell<Option<String>>,
+        /// The first page was asked for already.
+        pub(super) loaded: Cell<bool>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DewCommentsView {
+        const NAME: &'static str = "DewCommentsView";
+        type Type = super::DewCommentsView;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for DewCommentsView {}
+    impl WidgetImpl for DewCommentsView {}
+    impl BoxImpl for DewCommentsView {}
+
+    #[gtk::template_callbacks]
+    impl DewCommentsView {
+        #[template_callback]
+        pub(super) async fn load_more(&self) {
+            let Some(id) = self.video_id.borrow().clone() else {
+                return;
+            };
+            let window: DewDuctWindow =
+                self.obj().root().and_downcast().unwrap();
+            let continuation = self.continuation.borrow().clone();
+            let first_page = continuation.is_none();
+
+            self.more_button.set_sensitive(false);
+            if first_page {
+                self.stack.set_visible_child(&*self.spinner_page);
+            }
+            let comments =
+                fetch_comments(&window, id.clone(), continuation).await;
+            // another video might have been opened meanwhile.
+            if self.video_id.borrow().as_ref() != Some(&id) {
+                return;
+            }
+            self.more_button.set_sensitive(true);
+
+            let comments = match comments {
+                Ok(comments) => comments,
+                Err(err) => {
+                    g_warning!(
+                        "DewCommentsView",
+                        "can't load comments for {}: {:#}",
+                        id,
+                        err
+                    );
+                    if first_page {
+                        self.loaded.set(false);
+                        self.stack.set_visible_child(&*self.error_page);
+                    } else {
+                        window.toast("Can't load more comments");
+                    }
+                    return;
+                }
+            };
+
+            if first_page && comments.comments.is_empty() {
+                self.error_page.set_title("No Comments");
+                self.error_page.set_description(None);
+                self.stack.set_visible_child(&*self.error_page);
+                return;
+            }
+            for comment in &comments.comments {
+                let row = DewCommentRow::new();
+                row.set_from_comment(&id, comment);
+                self.comment_list.append(&row);
+            }
+            self.more_button
+                .set_visible(comments.continuation.is_some());
+            self.continuation.replace(comments.continuation);
+            self.stack.set_visible_child(&*self.comments_page);
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct DewCommentsView(ObjectSubclass<imp::DewCommentsView>)
+        @extends gtk::Widget, gtk::Box,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl DewCommentsView {
+    /// Forgets the comments of the last video. They are only loaded once
+    /// `load` is called.
+    pub fn set_video_id(&self, id: &str) {
+        let imp = self.imp();
+        imp.video_id.replace(Some(id.to_owned()));
+        imp.continuation.take();
+        imp.loaded.set(false);
+        while let Some(child) = imp.comment_list.first_child() {
+            imp.comment_list.remove(&child);
+        }
+        imp.more_button.set_visible(false);
+        imp.error_page.set_title("Can't Load Comments");
+        imp.error_page
+            .set_description(Some("Comments might be turned off."));
+    }
+    /// Loads the first page of comments, unless it was already.
+    pub async fn load(&self) {
+        if self.imp().loaded.replace(true) {
+            return;
+        }
+        self.imp().load_more().await
+    }
+}
+
+/// A page of comments for the video, or of replies to a comment if
+/// `continuation` is from `Replies`.
+pub(crate) async fn fetch_comments(
+    window: &DewDuctWindow,
+    id: String,
+    continuation: Option<String>,
+) -> anyhow::Result<Comments> {
+    let pool = window.instance_pool();
+    window
+        .spawn(async move {
+            pool.request(|invidious| {
+                let id = id.clone();
+                let params = continuation
+                    .as_ref()
+                    .map(|continuation| format!("continuation={continuation}"));
+                async move { invidious.comments(&id, params.as_deref()).await }
+            })
+            .await
+        })
+        .await?
+}
+
+/// How many replies a comment has, and the continuation for loading them.
+/// The fields of `Replies` are private, but they are serialized like the
+/// API gives them.
+pub(crate) fn reply_thread(replies: &Replies) -> Option<(u32, String)> {
+    let replies = serde_json::to_value(replies).ok()?;
+    let count = replies.get("replyCount")?.as_u64()? as u32;
+    let continuation = replies.get("continuation")?.as_str()?.to_owned();
+    Some((count, continuation))
+}
@@ -0,0 +1,440 @@
+/* description.rs
+ *
+ * Copyright 2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+//! Video descriptions, from the HTML Invidious gives to Pango markup for a
+//! `gtk::Label`, and what to do with the links in them.
+
+use html_escape::{
+    decode_html_entities, encode_double_quoted_attribute, encode_text,
+};
+
+/// Tags Pango knows, which are kept as they are.
+const KEPT_TAGS: [&str; 4] = ["b", "i", "s", "u"];
+
+/// Safe Pango markup for the description of the video `id`. Only links
+/// and simple formatting are kept; timestamps that are not links already
+/// become links to that point in the video.
+pub fn to_markup(html: &str, id: &str) -> String {
+    let mut markup = String::new();
+    // the tags that were opened in the markup, to close them properly.
+    let mut open_tags: Vec<&str> = vec![];
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        let (text, tag) = match rest.find('<') {
+            Some(start) => match rest[start..].find('>') {
+                Some(end) => {
+                    (&rest[..start], Some(&rest[start..start + end + 1]))
+                }
+                // never closed, so just a `<` in the text.
+                None => (rest, None),
+            },
+            None => (rest, None),
+        };
+        rest = &rest[text.len() + tag.map_or(0, str::len)..];
+
+        let in_link = open_tags.contains(&"a");
+        push_text(&mut markup, &decode_html_entities(text), id, in_link);
+
+        let Some(tag) = tag else { continue };
+        let tag = tag.trim_start_matches('<').trim_end_matches('>');
+        let (closing, tag) = match tag.strip_prefix('/') {
+            Some(tag) => (true, tag),
+            None => (false, tag.trim_end_matches('/')),
+        };
+        let name = tag
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        match (name.as_str(), closing) {
+            ("br", _) => markup.push('\n'),
+            (name, true) => {
+                if open_tags.last().is_some_and(|open| *open == name) {
+                    open_tags.pop();
+                    markup += &format!("</{name}>");
+                }
+            }
+            // links in links are not allowed.
+            ("a", false) if !in_link => {
+                let Some(href) = attribute(tag, "href") else {
+                    continue;
+                };
+                let href = decode_html_entities(&href);
+                markup += &format!(
+                    "<a href=\"{}\">",
+                    encode_double_quoted_attribute(&href)
+                );
+                open_tags.push("a");
+            }
+            (name, false) => {
+                if let Some(kept) = KEPT_TAGS.iter().find(|&&t| t == name) {
+                    markup += &format!("<{kept}>");
+                    open_tags.push(kept);
+                }
+            }
+        }
+    }
+    for tag in open_tags.into_iter().rev() {
+        markup += &format!("</{tag}>");
+    }
+    markup
+}
+
+/// Escapes the text, and makes links out of the timestamps in it.
+fn push_text(markup: &mut String, text: &str, id: &str, in_link: bool) {
+    let mut last = 0;
+    if !in_link {
+        for (start, end, seconds) in timestamps(text) {
+            *markup += &encode_text(&text[last..start]);
+            let href = format!(
+                "https://www.youtube.com/watch?v={}&amp;t={}s",
+                encode_double_quoted_attribute(id),
+                seconds
+            );
+            *markup +=
+                &format!("<a href=\"{href}\">{}</a>", &text[start..end]);
+            last = end;
+        }
+    }
+    *markup += &encode_text(&text[last..]);
+}
+
+/// The value of an attribute in the inside of a tag, like
+/// `a href="/watch?v=x"`.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let start = tag.find(&format!("{name}=\""))? + name.len() + 2;
+    let len = tag[start..].find('"')?;
+    Some(tag[start..start + len].to_owned())
+}
+
+/// Where the timestamps like `1:23` or `1:02:03` are in the text, and how
+/// many seconds in they point to.
+pub(crate) fn timestamps(text: &str) -> Vec<(usize, usize, u64)> {
+    let bytes = text.as_bytes();
+    let is_part = |i: usize| {
+        bytes
+            .get(i)
+            .is_some_and(|b| b.is_ascii_digit() || *b == b':')
+    };
+    let mut found = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        if !bytes[i].is_ascii_digit() || (i > 0 && is_part(i - 1)) {
+            i += 1;
+            continue;
+        }
+        let mut end = i;
+        while is_part(end) {
+            end += 1;
+        }
+        if let Some(seconds) = parse_timestamp(&text[i..end]) {
+            found.push((i, end, seconds));
+        }
+        i = end;
+    }
+    found
+}
+
+/// `1:23` or `1:02:03` in seconds. The minutes and seconds after the
+/// first part have two digits.
+fn parse_timestamp(timestamp: &str) -> Option<u64> {
+    let parts: Vec<&str> = timestamp.split(':').collect();
+    if !(2..=3).contains(&parts.len())
+        || parts[0].is_empty()
+        || parts[0].len() > 2
+        || parts[1..].iter().any(|part| part.len() != 2)
+    {
+        return None;
+    }
+    let parts: Vec<u64> = parts
+        .iter()
+        .map(|part| part.parse())
+        .collect::<Result<_, _>>()
+        .ok()?;
+    if parts[1..].iter().any(|part| *part > 59) {
+        return None;
+    }
+    Some(parts.iter().fold(0, |seconds, part| seconds * 60 + part))
+}
+
+/// Where a link in a description leads.
+#[derive(Debug, PartialEq, Eq)]
+pub enum DescriptionLink {
+    /// A point in the video the description is of, in seconds.
+    Seek(u64),
+    Video(String),
+    Channel(String),
+    /// Anything else, to open in the browser.
+    External(String),
+}
+
+impl DescriptionLink {
+    /// For a link in the description of the video `id`.
+    pub fn classify(uri: &str, id: &str) -> Self {
+        // the path and the query, for links to YouTube.
+        let link = ["https://", "http://"]
+            .iter()
+            .find_map(|scheme| uri.strip_prefix(scheme))
+            .and_then(|rest| {
+                let (host, link) =
+                    rest.split_at(rest.find('/').unwrap_or(rest.len()));
+                match host {
+                    "youtube.com" | "www.youtube.com" | "m.youtube.com" => {
+                        Some(link.to_owned())
+                    }
+                    // `youtu.be/ID?t=1` is `/watch?v=ID&t=1`.
+                    "youtu.be" => {
+                        let link = link.trim_start_matches('/');
+                        let (video, query) =
+                            link.split_once('?').unwrap_or((link, ""));
+                        Some(format!("/watch?v={video}&{query}"))
+                    }
+                    _ => None,
+                }
+            })
+            .or_else(|| uri.starts_with('/').then(|| uri.to_owned()));
+        let Some(link) = link else {
+            return Self::External(uri.to_owned());
+        };
+
+        let (path, query) = link.split_once('?').unwrap_or((&link, ""));
+        let param = |name: &str| {
+            query.split('&').find_map(|pair| {
+                let (key, value) = pair.split_once('=')?;
+                (key == name).then(|| {
+                    urlencoding::decode(value)
+                        .map_or(value.to_owned(), |value| {
+                            value.into_owned()
+                        })
+                })
+            })
+        };
+        let mut segments = path.split('/').filter(|s| !s.is_empty());
+
+        let video = match (segments.next(), segments.next()) {
+            (Some("watch"), _) => param("v"),
+            (Some("shorts" | "live" | "embed"), Some(video)) => {
+                Some(video.to_owned())
+            }
+            (Some("channel"), Some(channel)) => {
+                return Self::Channel(channel.to_owned());
+            }
+            // YouTube sends links to other sites through this.
+            (Some("redirect"), _) => match param("q") {
+                Some(target) => return Self::External(target),
+                None => None,
+            },
+            _ => None,
+        };
+        match video {
+            Some(video) if video == id => Self::Seek(
+                param("t").and_then(|t| parse_t(&t)).unwrap_or(0),
+            ),
+            Some(video) => Self::Video(video),
+            None => {
+                Self::External(format!("https://www.youtube.com{link}"))
+            }
+        }
+    }
+}
+
+/// The `t` parameter of a link, like `83`, `83s` or `1m23s`, in seconds.
+fn parse_t(t: &str) -> Option<u64> {
+    if let Ok(seconds) = t.parse() {
+        return Some(seconds);
+    }
+    let mut seconds = 0;
+    let mut number = String::new();
+    for c in t.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'h' | 'm' | 's' => {
+                let unit = match c {
+                    'h' => 3600,
+                    'm' => 60,
+                    _ => 1,
+                };
+                seconds += number.parse::<u64>().ok()? * unit;
+                number.clear();
+            }
+            _ => return None,
+        }
+    }
+    number.is_empty().then_some(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "dQw4w9WgXcQ";
+
+    fn classify(uri: &str) -> DescriptionLink {
+        DescriptionLink::classify(uri, ID)
+    }
+
+    #[test]
+    fn escapes_text() {
+        assert_eq!(
+            to_markup("Tom &amp; Jerry &lt;3 \"quotes\" & more", ID),
+            "Tom &amp; Jerry &lt;3 \"quotes\" &amp; more"
+        );
+        assert_eq!(
+            to_markup("a <b>bold</b> move", ID),
+            "a <b>bold</b> move"
+        );
+    }
+
+    #[test]
+    fn drops_unknown_tags() {
+        assert_eq!(
+            to_markup("<span foreground=\"red\">hi</span><script>x", ID),
+            "hix"
+        );
+        assert_eq!(
+            to_markup("<B>loud</B><br/>next", ID),
+            "<b>loud</b>\nnext"
+        );
+    }
+
+    #[test]
+    fn unbalanced_tags() {
+        // closing what was never opened does nothing.
+        assert_eq!(to_markup("one</b> two</a>", ID), "one two");
+        // what is left open is closed at the end, innermost first.
+        assert_eq!(to_markup("<b><i>both", ID), "<b><i>both</i></b>");
+        // a mismatched closing tag does not close the outer one.
+        assert_eq!(
+            to_markup("<b><i>both</b></i>", ID),
+            "<b><i>both</i></b>"
+        );
+    }
+
+    #[test]
+    fn links() {
+        assert_eq!(
+            to_markup("<a href=\"/watch?v=x&amp;t=1\">x</a>", ID),
+            "<a href=\"/watch?v=x&amp;t=1\">x</a>"
+        );
+        // quotes can't break out of the attribute.
+        assert_eq!(
+            to_markup("<a href=\"https://e.com/&quot;x\">e</a>", ID),
+            "<a href=\"https://e.com/&quot;x\">e</a>"
+        );
+        // links in links are dropped, their text kept.
+        assert_eq!(
+            to_markup("<a href=\"/a\"><a href=\"/b\">in</a></a>", ID),
+            "<a href=\"/a\">in</a>"
+        );
+        assert_eq!(to_markup("<a name=\"top\">top</a>", ID), "top");
+    }
+
+    #[test]
+    fn unterminated_tag() {
+        assert_eq!(to_markup("1 < 2, always", ID), "1 &lt; 2, always");
+        assert_eq!(to_markup("<b>x</b> <i", ID), "<b>x</b> &lt;i");
+    }
+
+    #[test]
+    fn timestamp_links() {
+        assert_eq!(
+            to_markup("skip to 1:23!", ID),
+            format!(
+                "skip to <a href=\"https://www.youtube.com/watch?v={ID}\
+                 &amp;t=83s\">1:23</a>!"
+            )
+        );
+        // already in a link, so left alone.
+        assert_eq!(
+            to_markup("<a href=\"/x\">1:23</a>", ID),
+            "<a href=\"/x\">1:23</a>"
+        );
+    }
+
+    #[test]
+    fn finds_timestamps() {
+        assert_eq!(
+            timestamps("0:00 intro, 1:02:03 end"),
+            [(0, 4, 0), (12, 19, 3723)]
+        );
+        // not times: out of range, too many digits, or too many parts.
+        assert!(timestamps("1:60 123:45 1:2 1:23:45:67").is_empty());
+    }
+
+    #[test]
+    fn youtube_links() {
+        assert_eq!(
+            classify("https://www.youtube.com/watch?v=abc"),
+            DescriptionLink::Video("abc".into())
+        );
+        assert_eq!(
+            classify("https://youtu.be/abc"),
+            DescriptionLink::Video("abc".into())
+        );
+        assert_eq!(
+            classify(&format!("https://youtu.be/{ID}?t=90")),
+            DescriptionLink::Seek(90)
+        );
+        assert_eq!(
+            classify("/shorts/abc"),
+            DescriptionLink::Video("abc".into())
+        );
+        assert_eq!(
+            classify("https://m.youtube.com/channel/UCxyz"),
+            DescriptionLink::Channel("UCxyz".into())
+        );
+        assert_eq!(
+            classify("/playlist?list=PL1"),
+            DescriptionLink::External(
+                "https://www.youtube.com/playlist?list=PL1".into()
+            )
+        );
+    }
+
+    #[test]
+    fn redirect_links() {
+        assert_eq!(
+            classify("https://www.youtube.com/redirect?event=x&q=https%3A%2F%2Fexample.com%2F%3Fa%3D1"),
+            DescriptionLink::External("https://example.com/?a=1".into())
+        );
+        assert_eq!(
+            classify("https://example.com/youtu.be/abc"),
+            DescriptionLink::External(
+                "https://example.com/youtu.be/abc".into()
+            )
+        );
+    }
+
+    #[test]
+    fn t_parameter() {
+        assert_eq!(parse_t("83"), Some(83));
+        assert_eq!(parse_t("83s"), Some(83));
+        assert_eq!(parse_t("1m23s"), Some(83));
+        assert_eq!(parse_t("1h2m3s"), Some(3723));
+        assert_eq!(parse_t("1m23"), None);
+        assert_eq!(parse_t("1x"), None);
+        assert_eq!(
+            classify(&format!("/watch?v={ID}&t=1m23s")),
+            DescriptionLink::Seek(83)
+        );
+    }
+}
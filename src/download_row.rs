@@ -0,0 +1,242 @@
+/* download_row.rs
+ *
+ * Copyright 2023-2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::{Cell, RefCell};
+
+#[allow(unused_imports)]
+use adw::{prelude::*, subclass::prelude::*};
+use glib::g_warning;
+use gtk::glib;
+#[allow(unused_imports)]
+use gtk::{prelude::*, subclass::prelude::*};
+
+use futures::StreamExt;
+
+use crate::downloads::{self, Download, DownloadEvent, DownloadSource};
+use crate::window::DewDuctWindow;
+use crate::yt_item_list::DewYtItem;
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, gtk::CompositeTemplate)]
+    #[template(resource = "/null/daknig/DewDuct/download_row.ui")]
+    pub struct DewDownloadRow {
+        // Template widgets
+        #[template_child]
+        title: TemplateChild<gtk::Label>,
+        #[template_child]
+        progress_bar: TemplateChild<gtk::ProgressBar>,
+        #[template_child]
+        status: TemplateChild<gtk::Label>,
+        #[template_child]
+        cancel_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        retry_button: TemplateChild<gtk::Button>,
+
+        pub(super) item: RefCell<Option<DewYtItem>>,
+        pub(super) source: RefCell<Option<DownloadSource>>,
+        /// The language and the URL of the captions to save with it.
+        pub(super) captions: RefCell<Option<(String, String)>>,
+        download: RefCell<Option<Download>>,
+        cancelled: Cell<bool>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DewDownloadRow {
+        const NAME: &'static str = "DewDownloadRow";
+        type Type = super::DewDownloadRow;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for DewDownloadRow {}
+    impl WidgetImpl for DewDownloadRow {}
+    impl BoxImpl for DewDownloadRow {}
+
+    #[gtk::template_callbacks]
+    impl DewDownloadRow {
+        fn window(&self) -> DewDuctWindow {
+            self.obj().root().and_downcast().unwrap()
+        }
+        pub(super) fn is_running(&self) -> bool {
+            self.download.borrow().is_some()
+        }
+        fn set_running(&self, running: bool) {
+            self.cancel_button.set_visible(running);
+            self.retry_button.set_visible(!running);
+        }
+        #[template_callback]
+        fn cancel(&self) {
+            if let Some(download) = self.download.borrow().as_ref() {
+                self.cancelled.set(true);
+                download.cancel();
+            }
+        }
+        /// Runs the download, with yt-dlp or directly, until it is done,
+        /// failed or cancelled.
+        #[template_callback]
+        pub(super) async fn start(&self) {
+            let Some(item) = self.item.borrow().clone() else {
+                return;
+            };
+            let Some(source) = self.source.borrow().clone() else {
+                return;
+            };
+            if self.is_running() {
+                return;
+            }
+            self.title.set_text(&item.title());
+            self.status.set_text("Starting…");
+            self.progress_bar.set_fraction(0.);
+            self.cancelled.set(false);
+
+            let mut events = match Download::start(&item.id(), &source) {
+                Ok((download, events)) => {
+                    self.download.replace(Some(download));
+                    events
+                }
+                Err(err) => {
+                    self.status.set_text(&err.to_string());
+                    self.set_running(false);
+                    return;
+                }
+            };
+            self.set_running(true);
+
+            while let Some(event) = events.next().await {
+                match event {
+                    DownloadEvent::Progress(fraction) => {
+                        self.progress_bar.set_fraction(fraction);
+                        self.status.set_text(&format!(
+                            "Downloading… {:.0}%",
+                            fraction * 100.
+                        ));
+                    }
+                    DownloadEvent::Done(path) => {
+                        self.progress_bar.set_fraction(1.);
+                        self.status.set_text("Downloaded");
+                        self.retry_button.set_visible(false);
+                        self.cancel_button.set_visible(false);
+                        self.download.take();
+                        self.save_captions(&item.id());
+                        self.window().add_to_library(&item, path);
+                        return;
+                    }
+                    DownloadEvent::Failed(err) => {
+                        if self.cancelled.get() {
+                            self.status.set_text("Cancelled");
+                        } else {
+                            g_warning!(
+                                "DewDownloadRow",
+                                "can't download {}: {}",
+                                item.id(),
+                                err
+                            );
+                            self.status.set_text(&err.to_string());
+                        }
+                        break;
+                    }
+                }
+            }
+            self.download.take();
+            self.set_running(false);
+        }
+        /// Next to the video, only once it is there.
+        fn save_captions(&self, id: &str) {
+            let Some((language, url)) = self.captions.borrow().clone()
+            else {
+                return;
+            };
+            let window = self.window();
+            let dest = downloads::captions_path(id, &language);
+            let saved = window.spawn_blocking(move || {
+                downloads::save_captions(&url, &dest)
+            });
+            glib::spawn_future_local(async move {
+                match saved.await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(err)) => {
+                        g_warning!(
+                            "DewDownloadRow",
+                            "can't save captions: {}",
+                            err
+                        );
+                        window.toast("Can't save the captions");
+                    }
+                    Err(err) => g_warning!("DewDownloadRow", "{}", err),
+                }
+            });
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct DewDownloadRow(ObjectSubclass<imp::DewDownloadRow>)
+        @extends gtk::Widget, gtk::Box,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl DewDownloadRow {
+    pub fn new(
+        item: &DewYtItem,
+        source: DownloadSource,
+        captions: Option<(String, String)>,
+    ) -> Self {
+        let ret: Self = glib::Object::builder().build();
+        ret.imp().item.replace(Some(item.clone()));
+        ret.imp().source.replace(Some(source));
+        ret.imp().captions.replace(captions);
+        ret
+    }
+    pub async fn start(&self) {
+        self.imp().start().await
+    }
+    /// For the next `start`. A running download can't change, so then
+    /// returns whether it is the same as the one running.
+    pub fn set_choice(
+        &self,
+        source: DownloadSource,
+        captions: Option<(String, String)>,
+    ) -> bool {
+        let imp = self.imp();
+        if imp.is_running() {
+            return imp.source.borrow().as_ref() == Some(&source)
+                && *imp.captions.borrow() == captions;
+        }
+        imp.source.replace(Some(source));
+        imp.captions.replace(captions);
+        true
+    }
+    pub fn is_running(&self) -> bool {
+        self.imp().is_running()
+    }
+    pub fn id(&self) -> Option<String> {
+        self.imp().item.borrow().as_ref().map(|item| item.id())
+    }
+}
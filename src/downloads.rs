@@ -0,0 +1,697 @@
+/* downloads.rs
+ *
+ * Copyright 2023-2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use glib::{g_debug, user_data_dir};
+use gtk::glib;
+
+use futures::channel::mpsc;
+use isahc::config::{Configurable, RedirectPolicy};
+use isahc::http::{header, HeaderMap, StatusCode};
+use isahc::Request;
+use lazy_static::lazy_static;
+use thiserror::Error;
+
+/// Marks the progress lines among the rest of the output of yt-dlp.
+const PROGRESS_PREFIX: &str = "dewduct-progress";
+
+lazy_static! {
+    /// Where the downloaded videos are kept.
+    pub static ref DOWNLOADS_DIR: PathBuf =
+        user_data_dir().join("DewDuct/").join("downloads");
+}
+
+#[derive(Error, Debug, Clone)]
+pub enum DownloadError {
+    #[error("yt-dlp is not installed")]
+    NotFound,
+    #[error("can't run yt-dlp: {0}")]
+    Io(String),
+    #[error("{0}")]
+    Failed(String),
+    #[error("can't fetch the video: {0}")]
+    Http(String),
+    #[error("expected {expected} bytes, got {got}")]
+    SizeMismatch { expected: u64, got: u64 },
+    #[error("cancelled")]
+    Cancelled,
+}
+
+impl From<isahc::Error> for DownloadError {
+    fn from(err: isahc::Error) -> Self {
+        Self::Http(err.to_string())
+    }
+}
+
+impl From<io::Error> for DownloadError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => Self::NotFound,
+            _ => Self::Io(err.to_string()),
+        }
+    }
+}
+
+/// What a download reports while it runs; `Done` or `Failed` come last.
+#[derive(Debug, Clone)]
+pub enum DownloadEvent {
+    /// Of the file being downloaded now, from 0 to 1; when the video and
+    /// the audio are separate, it goes up twice.
+    Progress(f64),
+    Done(PathBuf),
+    Failed(DownloadError),
+}
+
+/// The fraction out of a line printed with our progress template, of
+/// the form `dewduct-progress <downloaded> <total> <estimated total>`,
+/// where the totals are `NA` when unknown.
+pub fn parse_progress(line: &str) -> Option<f64> {
+    let mut fields = line
+        .trim()
+        .strip_prefix(PROGRESS_PREFIX)?
+        .split_whitespace()
+        .map(|field| field.parse::<f64>().ok());
+    let downloaded = fields.next()??;
+    let total = fields.flatten().find(|total| *total > 0.)?;
+    Some((downloaded / total).clamp(0., 1.))
+}
+
+/// What to download, and how.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadSource {
+    /// yt-dlp, with this `--format`.
+    Ytdl { format: String },
+    /// The stream itself, saved as `file_name` in `DOWNLOADS_DIR`.
+    Direct { url: String, file_name: String },
+}
+
+/// A running download, of either kind.
+pub enum Download {
+    Ytdl(YtdlDownload),
+    Direct(DirectDownload),
+}
+
+impl Download {
+    pub fn start(
+        id: &str,
+        source: &DownloadSource,
+    ) -> Result<(Self, mpsc::UnboundedReceiver<DownloadEvent>), DownloadError>
+    {
+        Ok(match source {
+            DownloadSource::Ytdl { format } => {
+                let (download, events) = YtdlDownload::start(id, format)?;
+                (Self::Ytdl(download), events)
+            }
+            DownloadSource::Direct { url, file_name } => {
+                fs::create_dir_all(&*DOWNLOADS_DIR)?;
+                let dest = DOWNLOADS_DIR.join(file_name);
+                let (download, events) = DirectDownload::start(url, dest);
+                (Self::Direct(download), events)
+            }
+        })
+    }
+
+    pub fn cancel(&self) {
+        match self {
+            Self::Ytdl(download) => download.cancel(),
+            Self::Direct(download) => download.cancel(),
+        }
+    }
+}
+
+/// yt-dlp, downloading one video into `DOWNLOADS_DIR`.
+pub struct YtdlDownload {
+    child: Arc<Mutex<Child>>,
+}
+
+impl YtdlDownload {
+    /// `format` is for `--format`, like the `ytdl_format` of a quality.
+    pub fn start(
+        id: &str,
+        format: &str,
+    ) -> Result<(Self, mpsc::UnboundedReceiver<DownloadEvent>), DownloadError>
+    {
+        std::fs::create_dir_all(&*DOWNLOADS_DIR)?;
+        let mut ytdl = Command::new("yt-dlp");
+        ytdl.arg("--newline")
+            .arg("--progress")
+            .arg("--no-playlist")
+            .arg("--no-simulate")
+            .args(["--format", format])
+            .arg("--progress-template")
+            .arg(format!(
+                "download:{PROGRESS_PREFIX} %(progress.downloaded_bytes)s \
+                 %(progress.total_bytes)s \
+                 %(progress.total_bytes_estimate)s"
+            ))
+            // the only thing printed besides the progress.
+            .args(["--print", "after_move:filepath"])
+            .arg("--paths")
+            .arg(&*DOWNLOADS_DIR)
+            .args(["--output", "%(id)s.%(ext)s"])
+            .arg(format!("https://youtube.com/watch?v={id}"))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let mut child = ytdl.spawn()?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let child = Arc::new(Mutex::new(child));
+        let (sender, receiver) = mpsc::unbounded();
+
+        let errors = thread::spawn(move || last_error(stderr));
+        let waited = child.clone();
+        thread::spawn(move || {
+            let mut path = None;
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                match parse_progress(&line) {
+                    Some(fraction) => {
+                        let _ = sender.unbounded_send(
+                            DownloadEvent::Progress(fraction),
+                        );
+                    }
+                    None if !line.trim().is_empty() => {
+                        path = Some(PathBuf::from(line.trim()))
+                    }
+                    None => {}
+                }
+            }
+            // stdout is closed, so yt-dlp is done or about to be.
+            let status = waited.lock().unwrap().wait();
+            let error = errors.join().ok().flatten();
+            let event = match (status, path) {
+                (Ok(status), Some(path)) if status.success() => {
+                    DownloadEvent::Done(path)
+                }
+                (Err(err), _) => DownloadEvent::Failed(err.into()),
+                (Ok(status), _) => DownloadEvent::Failed(
+                    DownloadError::Failed(error.unwrap_or_else(|| {
+                        format!("yt-dlp failed with {status}")
+                    })),
+                ),
+            };
+            let _ = sender.unbounded_send(event);
+        });
+
+        Ok((Self { child }, receiver))
+    }
+
+    /// Stops yt-dlp; the download then fails.
+    pub fn cancel(&self) {
+        if let Err(err) = self.child.lock().unwrap().kill() {
+            g_debug!("DewDownloads", "can't kill yt-dlp: {}", err);
+        }
+    }
+}
+
+/// The last `ERROR:` line of yt-dlp, without the prefix.
+fn last_error(stderr: impl Read) -> Option<String> {
+    BufReader::new(stderr)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| {
+            line.strip_prefix("ERROR:").map(|err| err.trim().to_owned())
+        })
+        .last()
+}
+
+/// A single stream fetched over HTTP, without yt-dlp. It goes into a
+/// `.part` file first, which later attempts continue from.
+pub struct DirectDownload {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl DirectDownload {
+    pub fn start(
+        url: &str,
+        dest: PathBuf,
+    ) -> (Self, mpsc::UnboundedReceiver<DownloadEvent>) {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::unbounded();
+        let url = url.to_owned();
+        let cancel = cancelled.clone();
+        thread::spawn(move || {
+            let result = fetch(&url, &dest, &cancel, |fraction| {
+                let _ = sender
+                    .unbounded_send(DownloadEvent::Progress(fraction));
+            });
+            let _ = sender.unbounded_send(match result {
+                Ok(()) => DownloadEvent::Done(dest),
+                Err(err) => DownloadEvent::Failed(err),
+            });
+        });
+        (Self { cancelled }, receiver)
+    }
+
+    /// Keeps the `.part` file, for trying again later.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+fn part_path(dest: &Path) -> PathBuf {
+    let mut part = dest.as_os_str().to_owned();
+    part.push(".part");
+    part.into()
+}
+
+/// The whole size, out of a `Content-Range` like `bytes 100-199/1000`
+/// or `bytes */1000`.
+fn range_total(headers: &HeaderMap) -> Option<u64> {
+    let range = headers.get(header::CONTENT_RANGE)?.to_str().ok()?;
+    range.rsplit_once('/')?.1.trim().parse().ok()
+}
+
+/// Where the body starts, out of a `Content-Range` like
+/// `bytes 100-199/1000`.
+fn range_start(headers: &HeaderMap) -> Option<u64> {
+    let range = headers.get(header::CONTENT_RANGE)?.to_str().ok()?;
+    let range = range.trim().strip_prefix("bytes")?.trim_start();
+    range.split_once('-')?.0.parse().ok()
+}
+
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// Downloads `url` into `dest`, continuing the `.part` file if there is
+/// one, and only renames it once it has all the bytes.
+fn fetch(
+    url: &str,
+    dest: &Path,
+    cancelled: &AtomicBool,
+    mut progress: impl FnMut(f64),
+) -> Result<(), DownloadError> {
+    let part = part_path(dest);
+    let offset = fs::metadata(&part).map_or(0, |meta| meta.len());
+
+    let request = Request::get(url)
+        .redirect_policy(RedirectPolicy::Follow)
+        .header(header::RANGE, format!("bytes={offset}-"))
+        .body(())
+        .map_err(|err| DownloadError::Http(err.to_string()))?;
+    let mut response = isahc::send(request)?;
+
+    let (mut file, mut done, total) = match response.status() {
+        StatusCode::PARTIAL_CONTENT => {
+            // anything but the rest of the file can't be appended.
+            let start = range_start(response.headers());
+            if start != Some(offset) {
+                return Err(DownloadError::Http(format!(
+                    "asked for the bytes from {offset}, got {start:?}"
+                )));
+            }
+            let file =
+                OpenOptions::new().create(true).append(true).open(&part)?;
+            (file, offset, range_total(response.headers()))
+        }
+        // the server ignored the range; start over.
+        StatusCode::OK => {
+            let file = File::create(&part)?;
+            (file, 0, content_length(response.headers()))
+        }
+        // nothing after the offset, which is fine if it is the end. The
+        // body is only an error message.
+        StatusCode::RANGE_NOT_SATISFIABLE => {
+            return match range_total(response.headers()) {
+                Some(expected) if expected == offset => {
+                    progress(1.);
+                    Ok(fs::rename(&part, dest)?)
+                }
+                Some(expected) => {
+                    // more than there should be can't be continued.
+                    if offset > expected {
+                        fs::remove_file(&part)?;
+                    }
+                    Err(DownloadError::SizeMismatch {
+                        expected,
+                        got: offset,
+                    })
+                }
+                None => Err(DownloadError::Http(
+                    StatusCode::RANGE_NOT_SATISFIABLE.to_string(),
+                )),
+            };
+        }
+        status => return Err(DownloadError::Http(status.to_string())),
+    };
+
+    let body = response.body_mut();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        if cancelled.load(Ordering::Relaxed) {
+            return Err(DownloadError::Cancelled);
+        }
+        let read = body.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buf[..read])?;
+        done += read as u64;
+        if let Some(total) = total.filter(|total| *total > 0) {
+            progress((done as f64 / total as f64).min(1.));
+        }
+    }
+    file.sync_all()?;
+    drop(file);
+
+    let got = fs::metadata(&part)?.len();
+    match total {
+        Some(expected) if got != expected => {
+            // more than there should be can't be continued.
+            if got > expected {
+                fs::remove_file(&part)?;
+            }
+            Err(DownloadError::SizeMismatch { expected, got })
+        }
+        _ => Ok(fs::rename(&part, dest)?),
+    }
+}
+
+/// Where the captions of a downloaded video go; mpv finds them there by
+/// itself, since the video is `<id>.<ext>`.
+pub fn captions_path(id: &str, language: &str) -> PathBuf {
+    DOWNLOADS_DIR.join(format!("{id}.{language}.vtt"))
+}
+
+/// Saves the captions at `url` into `dest`. They are small, so there is
+/// no progress and nothing to continue.
+pub fn save_captions(url: &str, dest: &Path) -> Result<(), DownloadError> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let request = Request::get(url)
+        .redirect_policy(RedirectPolicy::Follow)
+        .body(())
+        .map_err(|err| DownloadError::Http(err.to_string()))?;
+    let mut response = isahc::send(request)?;
+    if !response.status().is_success() {
+        return Err(DownloadError::Http(response.status().to_string()));
+    }
+    let mut file = File::create(dest)?;
+    io::copy(response.body_mut(), &mut file)?;
+    Ok(())
+}
+
+/// Deletes all the captions saved for the video `id`.
+pub fn remove_captions(id: &str) {
+    let Ok(entries) = fs::read_dir(&*DOWNLOADS_DIR) else {
+        return;
+    };
+    let prefix = format!("{id}.");
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(&prefix) && name.ends_with(".vtt") {
+            if let Err(err) = fs::remove_file(entry.path()) {
+                g_debug!("DewDownloads", "can't delete {}: {}", name, err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    /// How the fake server misbehaves.
+    #[derive(Clone, Copy, Default)]
+    struct Server {
+        /// Answers everything with the whole file.
+        ignore_range: bool,
+        /// Hangs up after that many bytes of the body.
+        cut_at: Option<usize>,
+        /// Claims the file is that much bigger than it is.
+        extra_total: u64,
+        /// Sends the whole file as a 206, whatever the range.
+        wrong_start: bool,
+    }
+
+    fn dummy_file() -> Vec<u8> {
+        (0..10_000u32).map(|i| (i % 251) as u8).collect()
+    }
+
+    /// Serves `dummy_file` for a single request; gives back the url, and
+    /// the `Range` header that was asked for.
+    fn serve(server: Server) -> (String, thread::JoinHandle<Option<u64>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url =
+            format!("http://{}/video.mp4", listener.local_addr().unwrap());
+        let peer = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut start = None;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                let line = line.to_ascii_lowercase();
+                if let Some(range) = line.strip_prefix("range: bytes=") {
+                    start = range.trim().trim_end_matches('-').parse().ok();
+                }
+            }
+
+            let file = dummy_file();
+            let total = file.len() as u64 + server.extra_total;
+            let offset = match server.ignore_range || server.wrong_start {
+                true => 0,
+                false => start.unwrap_or(0) as usize,
+            };
+            let mut stream = stream;
+            if offset >= file.len() {
+                let body = "nothing there";
+                write!(
+                    stream,
+                    "HTTP/1.1 416 Range Not Satisfiable\r\n\
+                     Content-Length: {}\r\n\
+                     Content-Range: bytes */{total}\r\n\
+                     Connection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+                return start;
+            }
+            let body = &file[offset..];
+            let head = match server.ignore_range {
+                true => format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n",
+                    body.len()
+                ),
+                false => format!(
+                    "HTTP/1.1 206 Partial Content\r\n\
+                     Content-Length: {}\r\n\
+                     Content-Range: bytes {offset}-{}/{total}\r\n",
+                    body.len(),
+                    file.len() - 1,
+                ),
+            };
+            write!(stream, "{head}Connection: close\r\n\r\n").unwrap();
+            let body = &body[..server.cut_at.unwrap_or(body.len())];
+            // the client may be gone already when cancelled.
+            let _ = stream.write_all(body);
+            start
+        });
+        (url, peer)
+    }
+
+    fn temp_dest(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("dewduct-downloads-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let dest = dir.join(name);
+        let _ = fs::remove_file(&dest);
+        let _ = fs::remove_file(part_path(&dest));
+        dest
+    }
+
+    fn download(
+        server: Server,
+        dest: &Path,
+    ) -> (Result<(), DownloadError>, Option<u64>, Vec<f64>) {
+        let (url, peer) = serve(server);
+        let mut fractions = vec![];
+        let result =
+            fetch(&url, dest, &AtomicBool::new(false), |fraction| {
+                fractions.push(fraction)
+            });
+        (result, peer.join().unwrap(), fractions)
+    }
+
+    #[test]
+    fn captions() {
+        let dest = temp_dest("captions.en.vtt");
+        let (url, peer) = serve(Server::default());
+        save_captions(&url, &dest).unwrap();
+        peer.join().unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), dummy_file());
+    }
+
+    #[test]
+    fn whole_file() {
+        let dest = temp_dest("whole.mp4");
+        let (result, range, fractions) = download(Server::default(), &dest);
+        result.unwrap();
+        assert_eq!(range, Some(0));
+        assert_eq!(fs::read(&dest).unwrap(), dummy_file());
+        assert!(!part_path(&dest).exists());
+        assert_eq!(fractions.last(), Some(&1.));
+    }
+
+    #[test]
+    fn resumes_from_part_file() {
+        let dest = temp_dest("resumed.mp4");
+        fs::write(part_path(&dest), &dummy_file()[..1000]).unwrap();
+        let (result, range, _) = download(Server::default(), &dest);
+        result.unwrap();
+        assert_eq!(range, Some(1000));
+        assert_eq!(fs::read(&dest).unwrap(), dummy_file());
+    }
+
+    #[test]
+    fn interrupted_then_resumed() {
+        let dest = temp_dest("interrupted.mp4");
+        let cut = Server {
+            cut_at: Some(3000),
+            ..Default::default()
+        };
+        let (result, _, _) = download(cut, &dest);
+        assert!(result.is_err());
+        assert!(!dest.exists());
+        let kept = fs::metadata(part_path(&dest)).unwrap().len();
+        assert!(kept <= 3000);
+
+        let (result, range, _) = download(Server::default(), &dest);
+        result.unwrap();
+        assert_eq!(range, Some(kept));
+        assert_eq!(fs::read(&dest).unwrap(), dummy_file());
+    }
+
+    #[test]
+    fn already_complete() {
+        let dest = temp_dest("complete.mp4");
+        fs::write(part_path(&dest), dummy_file()).unwrap();
+        let (result, range, fractions) = download(Server::default(), &dest);
+        result.unwrap();
+        assert_eq!(range, Some(10_000));
+        // the body of the 416 is not part of the file.
+        assert_eq!(fs::read(&dest).unwrap(), dummy_file());
+        assert_eq!(fractions, [1.]);
+    }
+
+    #[test]
+    fn part_file_too_long() {
+        let dest = temp_dest("too-long.mp4");
+        fs::write(part_path(&dest), [0; 10_500]).unwrap();
+        let (result, _, _) = download(Server::default(), &dest);
+        assert!(matches!(
+            result,
+            Err(DownloadError::SizeMismatch {
+                expected: 10_000,
+                got: 10_500
+            })
+        ));
+        assert!(!dest.exists());
+        assert!(!part_path(&dest).exists());
+    }
+
+    #[test]
+    fn unexpected_range() {
+        let dest = temp_dest("unexpected.mp4");
+        fs::write(part_path(&dest), &dummy_file()[..1000]).unwrap();
+        let wrong = Server {
+            wrong_start: true,
+            ..Default::default()
+        };
+        let (result, _, _) = download(wrong, &dest);
+        assert!(matches!(result, Err(DownloadError::Http(_))));
+        assert!(!dest.exists());
+        assert_eq!(fs::metadata(part_path(&dest)).unwrap().len(), 1000);
+    }
+
+    #[test]
+    fn range_ignored() {
+        let dest = temp_dest("ignored.mp4");
+        fs::write(part_path(&dest), b"not the start of the file").unwrap();
+        let ignore = Server {
+            ignore_range: true,
+            ..Default::default()
+        };
+        let (result, _, _) = download(ignore, &dest);
+        result.unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), dummy_file());
+    }
+
+    #[test]
+    fn size_mismatch() {
+        let dest = temp_dest("short.mp4");
+        let lying = Server {
+            extra_total: 10,
+            ..Default::default()
+        };
+        let (result, _, _) = download(lying, &dest);
+        assert!(matches!(
+            result,
+            Err(DownloadError::SizeMismatch {
+                expected: 10_010,
+                got: 10_000
+            })
+        ));
+        assert!(!dest.exists());
+        // what is there is kept, for the rest to be fetched later.
+        assert!(part_path(&dest).exists());
+    }
+
+    #[test]
+    fn cancelled() {
+        let dest = temp_dest("cancelled.mp4");
+        let (url, peer) = serve(Server::default());
+        let result = fetch(&url, &dest, &AtomicBool::new(true), |_| {});
+        assert!(matches!(result, Err(DownloadError::Cancelled)));
+        assert!(!dest.exists());
+        peer.join().unwrap();
+    }
+
+    #[test]
+    fn progress_lines() {
+        let line = "dewduct-progress 2500 10000 NA";
+        assert_eq!(parse_progress(line), Some(0.25));
+        let estimated = "dewduct-progress 500 NA 1000.0";
+        assert_eq!(parse_progress(estimated), Some(0.5));
+        assert_eq!(parse_progress("dewduct-progress 500 NA NA"), None);
+        assert_eq!(parse_progress("/videos/abc.mp4"), None);
+    }
+}
@@ -0,0 +1,145 @@
+/* downloads_page.rs
+ *
+ * Copyright 2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+#[allow(unused_imports)]
+use adw::{prelude::*, subclass::prelude::*};
+use glib::clone;
+use gtk::{gio, glib};
+#[allow(unused_imports)]
+use gtk::{prelude::*, subclass::prelude::*};
+
+use crate::download_row::DewDownloadRow;
+use crate::downloads::DownloadSource;
+use crate::yt_item_list::DewYtItem;
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, gtk::CompositeTemplate)]
+    #[template(resource = "/null/daknig/DewDuct/downloads_page.ui")]
+    pub struct DewDownloadsPage {
+        // Template widgets
+        #[template_child]
+        clear_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        downloads_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        empty_page: TemplateChild<adw::StatusPage>,
+        #[template_child]
+        jobs_page: TemplateChild<gtk::ScrolledWindow>,
+        #[template_child]
+        jobs: TemplateChild<gtk::ListBox>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DewDownloadsPage {
+        const NAME: &'static str = "DewDownloadsPage";
+        type Type = super::DewDownloadsPage;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for DewDownloadsPage {
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.update_empty();
+        }
+    }
+    impl WidgetImpl for DewDownloadsPage {}
+    impl BoxImpl for DewDownloadsPage {}
+
+    #[gtk::template_callbacks]
+    impl DewDownloadsPage {
+        fn rows(&self) -> Vec<DewDownloadRow> {
+            let mut ret = vec![];
+            let mut child = self.jobs.first_child();
+            while let Some(row) = child {
+                child = row.next_sibling();
+                let row = row.downcast::<gtk::ListBoxRow>().ok();
+                if let Some(download) =
+                    row.and_then(|row| row.child()).and_downcast()
+                {
+                    ret.push(download);
+                }
+            }
+            ret
+        }
+        fn update_empty(&self) {
+            if self.jobs.first_child().is_none() {
+                self.downloads_stack.set_visible_child(&*self.empty_page);
+            } else {
+                self.downloads_stack.set_visible_child(&*self.jobs_page);
+            }
+        }
+        /// Starts downloading, unless it is already being downloaded.
+        /// Returns false if it is, but not the way asked for.
+        pub(crate) fn add(
+            &self,
+            item: &DewYtItem,
+            source: DownloadSource,
+            captions: Option<(String, String)>,
+        ) -> bool {
+            let id = item.id();
+            if let Some(row) = self
+                .rows()
+                .into_iter()
+                .find(|row| row.id().as_deref() == Some(id.as_str()))
+            {
+                if !row.set_choice(source, captions) {
+                    return false;
+                }
+                glib::spawn_future_local(async move { row.start().await });
+                return true;
+            }
+
+            let row = DewDownloadRow::new(item, source, captions);
+            self.jobs.prepend(&row);
+            self.update_empty();
+            glib::spawn_future_local(clone!(@weak row =>
+                async move { row.start().await; }));
+            true
+        }
+        #[template_callback]
+        fn clear_finished(&self) {
+            for row in self.rows() {
+                if !row.is_running() {
+                    if let Some(parent) = row.parent() {
+                        self.jobs.remove(&parent);
+                    }
+                }
+            }
+            self.update_empty();
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct DewDownloadsPage(ObjectSubclass<imp::DewDownloadsPage>)
+        @extends gtk::Widget, gtk::Box,
+        @implements gio::ActionGroup, gio::ActionMap;
+}
@@ -0,0 +1,123 @@
+/* embedded_player.rs
+ *
+ * Copyright 2023-2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+#[allow(unused_imports)]
+use adw::{prelude::*, subclass::prelude::*};
+use gtk::{gio, glib};
+#[allow(unused_imports)]
+use gtk::{prelude::*, subclass::prelude::*};
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, gtk::CompositeTemplate)]
+    #[template(resource = "/null/daknig/DewDuct/embedded_player.ui")]
+    pub struct DewEmbeddedPlayer {
+        // Template widgets
+        #[template_child]
+        pub(super) picture: TemplateChild<gtk::Picture>,
+        #[template_child]
+        pub(super) controls: TemplateChild<gtk::MediaControls>,
+        #[template_child]
+        pub(super) fullscreen_button: TemplateChild<gtk::ToggleButton>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DewEmbeddedPlayer {
+        const NAME: &'static str = "DewEmbeddedPlayer";
+        type Type = super::DewEmbeddedPlayer;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for DewEmbeddedPlayer {}
+    impl WidgetImpl for DewEmbeddedPlayer {}
+    impl BoxImpl for DewEmbeddedPlayer {}
+
+    #[gtk::template_callbacks]
+    impl DewEmbeddedPlayer {
+        #[template_callback]
+        fn fullscreen_toggled(&self, button: &gtk::ToggleButton) {
+            let Some(window) =
+                self.obj().root().and_downcast::<gtk::Window>()
+            else {
+                return;
+            };
+            window.set_fullscreened(button.is_active());
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct DewEmbeddedPlayer(ObjectSubclass<imp::DewEmbeddedPlayer>)
+        @extends gtk::Widget, gtk::Box,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl DewEmbeddedPlayer {
+    /// Starts playing `url`, from `start` seconds in. Only streams with
+    /// both audio and video can be played here.
+    pub fn play(&self, url: &str, start: Option<f64>) -> gtk::MediaStream {
+        let media = gtk::MediaFile::for_file(&gio::File::for_uri(url));
+        if let Some(start) = start {
+            // seeking is only possible once the stream is ready.
+            media.connect_prepared_notify(move |media| {
+                if media.is_prepared() && media.is_seekable() {
+                    media.seek((start * 1e6) as i64);
+                }
+            });
+        }
+        self.imp().picture.set_paintable(Some(&media));
+        self.imp().controls.set_media_stream(Some(&media));
+        media.play();
+        media.upcast()
+    }
+
+    /// To `position` seconds in; false if nothing is playing.
+    pub fn seek(&self, position: f64) -> bool {
+        let Some(media) = self.imp().controls.media_stream() else {
+            return false;
+        };
+        if !media.is_seekable() {
+            return false;
+        }
+        media.seek((position * 1e6) as i64);
+        media.play();
+        true
+    }
+
+    pub fn stop(&self) {
+        let imp = self.imp();
+        if let Some(media) = imp.controls.media_stream() {
+            media.pause();
+        }
+        imp.controls.set_media_stream(None::<&gtk::MediaStream>);
+        imp.picture.set_paintable(None::<&gtk::gdk::Paintable>);
+        imp.fullscreen_button.set_active(false);
+    }
+}
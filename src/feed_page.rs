@@ -0,0 +1,276 @@
+/* feed_page.rs
+ *
+ * Copyright 2023-2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[allow(unused_imports)]
+use adw::{prelude::*, subclass::prelude::*};
+use glib::{clone, g_warning};
+use gtk::{gio, glib};
+#[allow(unused_imports)]
+use gtk::{prelude::*, subclass::prelude::*};
+
+use futures::StreamExt;
+use invidious::{ClientAsyncTrait, CommonVideo};
+use serde::{Deserialize, Serialize};
+
+use crate::util;
+use crate::window::DewDuctWindow;
+use crate::yt_item_list::*;
+
+/// Channels fetched more recently than that are not fetched again.
+const STALE_AFTER: Duration = Duration::from_secs(15 * 60);
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, gtk::CompositeTemplate)]
+    #[template(resource = "/null/daknig/DewDuct/feed_page.ui")]
+    pub struct DewFeedPage {
+        // Template widgets
+        #[template_child]
+        update_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        feed_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        empty_page: TemplateChild<adw::StatusPage>,
+        #[template_child]
+        vid_list: TemplateChild<DewYtItemList>,
+
+        /// Loaded from disk on the first update.
+        feed: RefCell<Option<Feed>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DewFeedPage {
+        const NAME: &'static str = "DewFeedPage";
+        type Type = super::DewFeedPage;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for DewFeedPage {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            // fetching every channel is expensive- only do it once the
+            // user actually looks at the feed.
+            self.obj().connect_map(|page| {
+                glib::spawn_future_local(clone!(@weak page =>
+                    async move { page.imp().update_vids().await; }));
+            });
+            // whatever is there now, the user has seen.
+            self.obj().connect_unmap(|page| {
+                glib::spawn_future_local(clone!(@weak page =>
+                    async move { page.imp().mark_visited().await; }));
+            });
+        }
+    }
+    impl WidgetImpl for DewFeedPage {}
+    impl BoxImpl for DewFeedPage {}
+
+    #[gtk::template_callbacks]
+    impl DewFeedPage {
+        fn window(&self) -> DewDuctWindow {
+            self.obj().root().and_downcast().unwrap()
+        }
+        async fn load_feed(&self) -> Feed {
+            if let Some(feed) = self.feed.take() {
+                return feed;
+            }
+            let path = util::data_path("feed.json");
+            self.window()
+                .spawn_blocking(move || util::load_state(&path))
+                .await
+                .unwrap_or_default()
+        }
+        async fn store_feed(&self, feed: Feed) {
+            let path = util::data_path("feed.json");
+            let feed = self
+                .window()
+                .spawn_blocking(move || {
+                    if let Err(err) = util::store_state(&path, &feed) {
+                        g_warning!("DewFeedPage", "{:#}", err);
+                    }
+                    feed
+                })
+                .await;
+            if let Ok(feed) = feed {
+                self.feed.replace(Some(feed));
+            }
+        }
+        async fn mark_visited(&self) {
+            let mut feed = self.load_feed().await;
+            feed.last_visit = now();
+            self.store_feed(feed).await;
+        }
+        #[template_callback]
+        async fn update_vids(&self) {
+            if !self.update_button.is_sensitive() {
+                return; // already updating
+            }
+            self.update_button.set_sensitive(false);
+
+            let ids: Vec<String> = self
+                .window()
+                .subscriptions()
+                .into_iter()
+                .map(|channel| channel.id())
+                .collect();
+            let mut feed = self.load_feed().await;
+            feed.channels.retain(|id, _| ids.contains(id));
+
+            let stale: Vec<String> = ids
+                .into_iter()
+                .filter(|id| {
+                    feed.channels.get(id).is_none_or(|channel| {
+                        channel.fetched + STALE_AFTER.as_secs() < now()
+                    })
+                })
+                .collect();
+            let n_stale = stale.len();
+
+            let pool = self.window().instance_pool();
+            let channels = self
+                .window()
+                .spawn(async move {
+                    futures::stream::iter(stale)
+                        .map(|id| {
+                            let pool = pool.clone();
+                            async move {
+                                pool.request(|invidious| {
+                                    let id = id.clone();
+                                    async move {
+                                        invidious.channel(&id, None).await
+                                    }
+                                })
+                                .await
+                                .map_err(|err| {
+                                    g_warning!(
+                                        "DewFeedPage",
+                                        "cant update channel: {:#}",
+                                        err
+                                    )
+                                })
+                                .ok()
+                            }
+                        })
+                        .buffer_unordered(10)
+                        .filter_map(futures::future::ready)
+                        .collect::<Vec<_>>()
+                        .await
+                })
+                .await
+                .unwrap_or_default();
+
+            if channels.len() < n_stale {
+                self.update_button.add_css_class("error");
+            } else {
+                self.update_button.remove_css_class("error");
+            }
+
+            // the user might have left the page while we were fetching.
+            if let Some(stored) = self.feed.take() {
+                feed.last_visit = feed.last_visit.max(stored.last_visit);
+            }
+            let fetched = now();
+            for channel in channels {
+                feed.channels.insert(
+                    channel.id,
+                    FeedChannel {
+                        fetched,
+                        videos: channel.lastest_videos,
+                    },
+                );
+            }
+
+            self.show_feed(&feed);
+            self.store_feed(feed).await;
+            self.update_button.set_sensitive(true);
+        }
+        fn show_feed(&self, feed: &Feed) {
+            let mut vids: Vec<&CommonVideo> = feed
+                .channels
+                .values()
+                .flat_map(|channel| channel.videos.iter())
+                .collect();
+            if vids.is_empty() {
+                self.feed_stack.set_visible_child(&*self.empty_page);
+                return;
+            }
+            vids.sort_unstable_by_key(|vid| {
+                std::cmp::Reverse(vid.published)
+            });
+
+            let items = vids
+                .into_iter()
+                .map(|vid| {
+                    let item: DewYtItem = vid.into();
+                    // on the first visit ever, nothing is new.
+                    item.set_is_new(
+                        feed.last_visit != 0
+                            && vid.published > feed.last_visit,
+                    );
+                    item
+                })
+                .collect();
+            self.vid_list.set_from_vec(items);
+            self.feed_stack.set_visible_child(&*self.vid_list);
+        }
+    }
+
+    /// What is stored on disk: the last fetched videos of each channel.
+    #[derive(Default, Deserialize, Serialize)]
+    pub(super) struct Feed {
+        /// When the user last left the feed, in seconds since the epoch.
+        last_visit: u64,
+        channels: HashMap<String, FeedChannel>,
+    }
+
+    #[derive(Deserialize, Serialize)]
+    pub(super) struct FeedChannel {
+        /// In seconds since the epoch.
+        fetched: u64,
+        videos: Vec<CommonVideo>,
+    }
+}
+
+glib::wrapper! {
+    pub struct DewFeedPage(ObjectSubclass<imp::DewFeedPage>)
+        @extends gtk::Widget, gtk::Box,
+        @implements gio::ActionGroup, gio::ActionMap;
+}
@@ -0,0 +1,247 @@
+/* history_page.rs
+ *
+ * Copyright 2023-2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[allow(unused_imports)]
+use adw::{prelude::*, subclass::prelude::*};
+use glib::{clone, g_warning, Variant};
+use gtk::{gio, glib};
+#[allow(unused_imports)]
+use gtk::{prelude::*, subclass::prelude::*};
+
+use serde::{Deserialize, Serialize};
+
+use crate::util;
+use crate::window::DewDuctWindow;
+use crate::yt_item_list::*;
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, gtk::CompositeTemplate)]
+    #[template(resource = "/null/daknig/DewDuct/history_page.ui")]
+    pub struct DewHistoryPage {
+        // Template widgets
+        #[template_child]
+        clear_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        history_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        empty_page: TemplateChild<adw::StatusPage>,
+        #[template_child]
+        vid_list: TemplateChild<DewYtItemList>,
+        #[template_child]
+        row_menu: TemplateChild<gio::MenuModel>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DewHistoryPage {
+        const NAME: &'static str = "DewHistoryPage";
+        type Type = super::DewHistoryPage;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+            klass.install_action(
+                "history.remove",
+                Some(glib::VariantTy::STRING),
+                Self::Type::remove,
+            );
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for DewHistoryPage {
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.vid_list.set_row_menu(&*self.row_menu);
+            self.vid_list.connect_items_changed(
+                clone!(@weak self as page => move |list| {
+                    let empty = list.n_items() == 0;
+                    page.clear_button.set_sensitive(!empty);
+                    if empty {
+                        page.history_stack
+                            .set_visible_child(&*page.empty_page);
+                    } else {
+                        page.history_stack
+                            .set_visible_child(&*page.vid_list);
+                    }
+                }),
+            );
+            glib::spawn_future_local(clone!(@weak self as page =>
+                 async move { page.load_state().await; }));
+        }
+    }
+    impl WidgetImpl for DewHistoryPage {}
+    impl BoxImpl for DewHistoryPage {}
+
+    #[gtk::template_callbacks]
+    impl DewHistoryPage {
+        fn window(&self) -> DewDuctWindow {
+            self.obj().root().and_downcast().unwrap()
+        }
+        fn store_state(&self) {
+            let path = util::data_path("history.json");
+            let history = History {
+                entries: self
+                    .vid_list
+                    .get_vec()
+                    .into_iter()
+                    .map(|item| item.into())
+                    .collect(),
+            };
+            if let Err(err) = util::store_state(&path, &history) {
+                g_warning!("DewHistoryPage", "{:#}", err);
+            }
+        }
+        async fn load_state(&self) {
+            let path = util::data_path("history.json");
+            let history: History = self
+                .window()
+                .spawn_blocking(move || util::load_state(&path))
+                .await
+                .unwrap_or_default();
+
+            // anything played while loading is more recent.
+            let mut items: Vec<DewYtItem> =
+                self.vid_list.get_vec().into_iter().collect();
+            let loaded: Vec<DewYtItem> = history
+                .entries
+                .into_iter()
+                .filter(|entry| !items.iter().any(|i| i.id() == entry.id))
+                .map(DewYtItem::from)
+                .collect();
+            items.extend(loaded);
+            self.vid_list.set_from_vec(items);
+        }
+        /// Puts the video on top of the history, with the current time.
+        pub(crate) fn add(&self, item: &DewYtItem) {
+            let entry = HistoryEntry {
+                watched: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+                ..item.clone().into()
+            };
+            let mut items: Vec<DewYtItem> = self
+                .vid_list
+                .get_vec()
+                .into_iter()
+                .filter(|old| old.id() != entry.id)
+                .collect();
+            items.insert(0, entry.into());
+            self.vid_list.set_from_vec(items);
+            self.store_state();
+        }
+        pub(super) fn remove(&self, id: String) {
+            self.vid_list.del_item_with_id(id);
+            self.store_state();
+        }
+        #[template_callback]
+        async fn clear_history(&self) {
+            let dialog = adw::MessageDialog::new(
+                Some(&self.window()),
+                Some("Clear History?"),
+                Some("All the videos you played will be forgotten."),
+            );
+            dialog.add_responses(&[
+                ("cancel", "_Cancel"),
+                ("clear", "_Clear"),
+            ]);
+            dialog.set_response_appearance(
+                "clear",
+                adw::ResponseAppearance::Destructive,
+            );
+            dialog.set_default_response(Some("cancel"));
+            dialog.set_close_response("cancel");
+
+            if dialog.choose_future().await.as_str() == "clear" {
+                self.vid_list.set_from_vec(vec![]);
+                self.store_state();
+            }
+        }
+    }
+
+    #[derive(Default, Deserialize, Serialize)]
+    pub(super) struct History {
+        /// Most recently watched first.
+        entries: Vec<HistoryEntry>,
+    }
+
+    #[derive(Deserialize, Serialize)]
+    pub(super) struct HistoryEntry {
+        id: String,
+        title: String,
+        author: String,
+        thumbnails: Vec<Thumbnail>,
+        length: u64,
+        /// When it was last played, in seconds since the epoch.
+        watched: u64,
+    }
+
+    // the row shows the `published` time; in the history, it is more
+    // useful to know when the video was watched.
+    impl From<DewYtItem> for HistoryEntry {
+        fn from(item: DewYtItem) -> Self {
+            Self {
+                id: item.id(),
+                title: item.title(),
+                author: item.author(),
+                thumbnails: item.thumbnails().to_vec(),
+                length: item.length(),
+                watched: item.published(),
+            }
+        }
+    }
+    impl From<HistoryEntry> for DewYtItem {
+        fn from(entry: HistoryEntry) -> Self {
+            let ret: Self = glib::Object::builder()
+                .property("author", entry.author)
+                .property("id", entry.id)
+                .property("length", entry.length)
+                .property("published", entry.watched)
+                .property("title", entry.title)
+                .build();
+            ret.set_thumbnails(entry.thumbnails);
+            ret
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct DewHistoryPage(ObjectSubclass<imp::DewHistoryPage>)
+        @extends gtk::Widget, gtk::Box,
+        @implements gio::ActionGroup, gio::ActionMap;
+}
+
+impl DewHistoryPage {
+    pub fn remove(&self, _: &str, param: Option<&Variant>) {
+        let Some(id) = param.and_then(|param| param.get::<String>()) else {
+            g_warning!("DewHistoryPage", "history.remove needs an id");
+            return;
+        };
+        self.imp().remove(id);
+    }
+}
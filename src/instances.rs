@@ -0,0 +1,552 @@
+/* instances.rs
+ *
+ * Copyright 2023-2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::future::Future;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use glib::{g_debug, g_warning};
+use gtk::{gio, glib};
+
+use anyhow::Context;
+use futures::StreamExt;
+use invidious::universal::Stats;
+use invidious::{ClientAsync, InvidiousError, MethodAsync, PublicItems};
+use serde::{Deserialize, Serialize};
+
+use crate::cache::DewCache;
+use crate::util::{cache, cache_dir};
+
+/// How many instances a single request is tried against.
+const MAX_ATTEMPTS: usize = 3;
+/// Failures in a row before an instance is moved to the back of the pool.
+const MAX_FAILURES: u32 = 3;
+
+#[derive(Debug, Clone)]
+struct InstanceHealth {
+    url: String,
+    /// Failures in a row, reset on every success.
+    failures: u32,
+}
+
+/// The Invidious instances known to the app, best first. Requests are
+/// tried against the first instance, and on failure against the next ones.
+/// Cheap to clone; all the clones share the same health information.
+#[derive(Debug, Clone, Default)]
+pub struct DewInstancePool {
+    instances: Arc<Mutex<Vec<InstanceHealth>>>,
+}
+
+impl DewInstancePool {
+    /// The instance that would be used for the next request.
+    pub fn instance(&self) -> String {
+        let instances = self.instances.lock().unwrap();
+        instances
+            .first()
+            .map(|instance| instance.url.clone())
+            .unwrap_or_else(|| crate::settings::DEFAULT_INSTANCE.into())
+    }
+
+    /// Puts the instance first, with a clean slate.
+    pub fn set_preferred(&self, url: String) {
+        let mut instances = self.instances.lock().unwrap();
+        instances.retain(|instance| instance.url != url);
+        instances.insert(0, InstanceHealth { url, failures: 0 });
+    }
+
+    /// Appends instances not already in the pool.
+    pub fn add_fallbacks<'a>(
+        &self,
+        urls: impl IntoIterator<Item = &'a str>,
+    ) {
+        let mut instances = self.instances.lock().unwrap();
+        for url in urls {
+            if !instances.iter().any(|instance| instance.url == url) {
+                instances.push(InstanceHealth {
+                    url: url.to_owned(),
+                    failures: 0,
+                });
+            }
+        }
+    }
+
+    /// Reorders everything but the preferred instance to follow `ranked`.
+    /// Instances missing from `ranked` go last.
+    pub fn rank_fallbacks<'a>(
+        &self,
+        ranked: impl IntoIterator<Item = &'a str>,
+    ) {
+        let ranked: Vec<&str> = ranked.into_iter().collect();
+        self.add_fallbacks(ranked.iter().copied());
+
+        let mut instances = self.instances.lock().unwrap();
+        if let Some((_, fallbacks)) = instances.split_first_mut() {
+            fallbacks.sort_by_key(|instance| {
+                ranked
+                    .iter()
+                    .position(|url| *url == instance.url)
+                    .unwrap_or(usize::MAX)
+            });
+        }
+    }
+
+    fn candidates(&self) -> Vec<String> {
+        let instances = self.instances.lock().unwrap();
+        instances
+            .iter()
+            .take(MAX_ATTEMPTS)
+            .map(|instance| instance.url.clone())
+            .collect()
+    }
+
+    fn report_success(&self, url: &str) {
+        let mut instances = self.instances.lock().unwrap();
+        if let Some(instance) = instances.iter_mut().find(|i| i.url == url)
+        {
+            instance.failures = 0;
+        }
+    }
+
+    fn report_failure(&self, url: &str) {
+        let mut instances = self.instances.lock().unwrap();
+        let Some(pos) = instances.iter().position(|i| i.url == url) else {
+            return;
+        };
+        instances[pos].failures += 1;
+        if instances[pos].failures >= MAX_FAILURES {
+            g_warning!(
+                "DewInstancePool",
+                "{} failed {} times in a row, demoting it",
+                url,
+                instances[pos].failures
+            );
+            let mut demoted = instances.remove(pos);
+            demoted.failures = 0;
+            instances.push(demoted);
+        }
+    }
+
+    /// Runs the request against the healthiest instances until one
+    /// succeeds. Only network and parsing errors count against an
+    /// instance; errors reported by the API itself are still retried
+    /// elsewhere, but are not held against the instance.
+    pub async fn request<T, F, Fut>(&self, f: F) -> anyhow::Result<T>
+    where
+        F: Fn(ClientAsync) -> Fut,
+        Fut: Future<Output = Result<T, InvidiousError>>,
+    {
+        Ok(self.request_from(f).await?.0)
+    }
+
+    /// Same as `request`, but also gives back the instance that answered.
+    /// Relative URLs in the response only work on that instance.
+    pub async fn request_from<T, F, Fut>(
+        &self,
+        f: F,
+    ) -> anyhow::Result<(T, String)>
+    where
+        F: Fn(ClientAsync) -> Fut,
+        Fut: Future<Output = Result<T, InvidiousError>>,
+    {
+        let mut last_err = anyhow::anyhow!("no instances to use");
+        for url in self.candidates() {
+            let client =
+                ClientAsync::new(url.clone(), MethodAsync::default());
+            match f(client).await {
+                Ok(ret) => {
+                    self.report_success(&url);
+                    return Ok((ret, url));
+                }
+                Err(err) => {
+                    g_warning!(
+                        "DewInstancePool",
+                        "{} failed: {}",
+                        url,
+                        err
+                    );
+                    if !matches!(err, InvidiousError::ApiError { .. }) {
+                        self.report_failure(&url);
+                    }
+                    last_err = anyhow::anyhow!("{url}: {err}");
+                }
+            }
+        }
+        Err(last_err)
+    }
+}
+
+/// The public list of instances, as maintained by the Invidious project.
+pub const INSTANCES_LIST_URL: &str =
+    "https://api.invidious.io/instances.json?sort_by=health";
+/// How long the ranking is kept on disk before probing again.
+const RANKING_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+const LIST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An instance that answered the probe, and how fast it did so.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RankedInstance {
+    pub url: String,
+    pub latency_ms: u64,
+    pub version: String,
+}
+
+/// An entry of the public list; it comes as `[name, details]`.
+#[derive(Deserialize)]
+struct ListedInstance {
+    uri: String,
+    r#type: String,
+    #[serde(default)]
+    api: Option<bool>,
+}
+
+/// Only https instances with the API enabled are of any use to us.
+fn parse_instance_list(json: &[u8]) -> anyhow::Result<Vec<String>> {
+    let list: Vec<(String, ListedInstance)> = serde_json::from_slice(json)?;
+    Ok(list
+        .into_iter()
+        .filter(|(_, instance)| {
+            instance.r#type == "https" && instance.api == Some(true)
+        })
+        .map(|(_, instance)| instance.uri.trim_end_matches('/').to_owned())
+        .collect())
+}
+
+/// The copy of the list shipped with the app, for when the real one is
+/// unreachable.
+fn bundled_instance_list() -> Vec<String> {
+    gio::resources_lookup_data(
+        "/null/daknig/DewDuct/instances.json",
+        gio::ResourceLookupFlags::NONE,
+    )
+    .context("no bundled instance list")
+    .and_then(|data| parse_instance_list(&data))
+    .unwrap_or_else(|err| {
+        g_warning!("DewInstancePool", "{:#}", err);
+        vec![]
+    })
+}
+
+async fn fetch_instance_list(
+    list_url: &str,
+) -> anyhow::Result<Vec<String>> {
+    use isahc::config::Configurable;
+    use isahc::{AsyncReadResponseExt, Request, RequestExt};
+
+    let mut response = Request::get(list_url)
+        .timeout(LIST_TIMEOUT)
+        .body(())?
+        .send_async()
+        .await?;
+    let contents = response.bytes().await?;
+    parse_instance_list(&contents)
+        .with_context(|| format!("malformed instance list at {list_url}"))
+}
+
+/// Checks that `/api/v1/stats` answers in time, with something that parses.
+async fn probe(instance: String) -> Option<RankedInstance> {
+    use isahc::config::Configurable;
+    use isahc::{AsyncReadResponseExt, Request, RequestExt};
+
+    let start = Instant::now();
+    let request = Request::get(format!("{instance}/api/v1/stats"))
+        .timeout(PROBE_TIMEOUT)
+        .body(())
+        .ok()?;
+    let mut response = request.send_async().await.ok()?;
+    if !response.status().is_success() {
+        g_debug!("DewInstancePool", "{instance}: {}", response.status());
+        return None;
+    }
+    let stats = Stats::from_str(response.text().await.ok()?).ok()?;
+
+    Some(RankedInstance {
+        url: instance,
+        latency_ms: start.elapsed().as_millis() as u64,
+        version: stats.software.version,
+    })
+}
+
+/// Probes every instance from the list at `list_url` (or from the bundled
+/// copy, if that fails) and sorts the ones that answered, fastest first.
+pub async fn discover(list_url: &str) -> Vec<RankedInstance> {
+    let candidates = match fetch_instance_list(list_url).await {
+        Ok(candidates) if !candidates.is_empty() => candidates,
+        Ok(_) => bundled_instance_list(),
+        Err(err) => {
+            g_warning!(
+                "DewInstancePool",
+                "can't fetch instance list: {:#}",
+                err
+            );
+            bundled_instance_list()
+        }
+    };
+
+    let mut ranked: Vec<RankedInstance> = futures::stream::iter(candidates)
+        .map(probe)
+        .buffer_unordered(10)
+        .filter_map(futures::future::ready)
+        .collect()
+        .await;
+    ranked.sort_by_key(|instance| instance.latency_ms);
+    ranked
+}
+
+/// Same as `discover`, but the ranking is kept in the cache for a day.
+pub async fn discover_cached() -> anyhow::Result<Vec<RankedInstance>> {
+    let fname = cache_dir(Path::new("instances.json"));
+    let contents = DewCache::fetch_file_max_age(
+        cache(),
+        fname,
+        RANKING_MAX_AGE,
+        |fname| {
+            let fname = fname.to_owned();
+            async move {
+                let ranked = discover(INSTANCES_LIST_URL).await;
+                if ranked.is_empty() {
+                    anyhow::bail!("no instance answered");
+                }
+                let contents = serde_json::to_vec(&ranked)?;
+                DewCache::store(&fname, &contents);
+                anyhow::Ok(contents)
+            }
+        },
+    )
+    .await?;
+    Ok(serde_json::from_slice(&contents)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use serde_json::{json, Value};
+    use std::cell::RefCell;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    const STATS: &str = r#"{
+        "version": "2.0",
+        "software": {"name": "invidious", "version": "2024.04.26", "branch": "master"},
+        "openRegistrations": true,
+        "usage": {"users": {"total": 1, "activeHalfyear": 1, "activeMonth": 1}},
+        "metadata": {"updatedAt": 0, "lastChannelRefreshedAt": 0}
+    }"#;
+
+    /// Answers every request with `status` and `body`, after `delay`.
+    /// Gives back the url of the server.
+    fn serve(
+        status: &'static str,
+        body: String,
+        delay: Duration,
+    ) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut reader =
+                    BufReader::new(stream.try_clone().unwrap());
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0
+                        || line.trim().is_empty()
+                    {
+                        break;
+                    }
+                }
+                thread::sleep(delay);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\n\
+                     Connection: close\r\n\r\n{body}",
+                    body.len()
+                );
+            }
+        });
+        url
+    }
+
+    /// A url nobody listens on.
+    fn dead() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    }
+
+    fn listed(uri: &str, r#type: &str, api: Option<bool>) -> Value {
+        json!([uri, {"uri": uri, "type": r#type, "api": api}])
+    }
+
+    #[test]
+    fn instance_list() {
+        let list = json!([
+            listed("https://good.example/", "https", Some(true)),
+            listed("https://no-api.example", "https", Some(false)),
+            listed("https://unknown.example", "https", None),
+            listed("http://onion.example", "onion", Some(true)),
+        ]);
+        let parsed = parse_instance_list(list.to_string().as_bytes());
+        assert_eq!(parsed.unwrap(), ["https://good.example"]);
+        assert!(parse_instance_list(b"<html>").is_err());
+    }
+
+    #[test]
+    fn ranked_by_latency() {
+        let ok = |delay| serve("200 OK", STATS.into(), delay);
+        let slow = ok(Duration::from_millis(300));
+        let fast = ok(Duration::ZERO);
+        let broken = serve(
+            "500 Internal Server Error",
+            STATS.into(),
+            Duration::ZERO,
+        );
+        let garbage = serve("200 OK", "<html>".into(), Duration::ZERO);
+        let gone = dead();
+
+        let list = json!([&slow, &broken, &garbage, &gone, &fast]
+            .iter()
+            .map(|uri| listed(uri, "https", Some(true)))
+            .collect::<Vec<_>>());
+        let list_url = serve("200 OK", list.to_string(), Duration::ZERO);
+
+        let ranked = block_on(discover(&format!("{list_url}/list.json")));
+        let urls: Vec<&str> = ranked
+            .iter()
+            .map(|instance| instance.url.as_str())
+            .collect();
+        assert_eq!(urls, [fast.as_str(), slow.as_str()]);
+        assert!(ranked[0].latency_ms <= ranked[1].latency_ms);
+        assert_eq!(ranked[0].version, "2024.04.26");
+    }
+
+    fn pool(urls: &[&str]) -> DewInstancePool {
+        let pool = DewInstancePool::default();
+        pool.add_fallbacks(urls.iter().copied());
+        pool
+    }
+
+    fn order(pool: &DewInstancePool) -> Vec<String> {
+        let instances = pool.instances.lock().unwrap();
+        instances
+            .iter()
+            .map(|instance| instance.url.clone())
+            .collect()
+    }
+
+    fn unreachable() -> InvidiousError {
+        InvidiousError::Message {
+            message: "unreachable".into(),
+        }
+    }
+
+    /// Runs a request that fails on `broken` with `err`, and succeeds
+    /// elsewhere with the url of the instance. Gives back the instances
+    /// tried, in order.
+    fn run(
+        pool: &DewInstancePool,
+        broken: &[&str],
+        err: fn() -> InvidiousError,
+    ) -> (anyhow::Result<(String, String)>, Vec<String>) {
+        let tried = RefCell::new(vec![]);
+        let ret = block_on(pool.request_from(|client| {
+            tried.borrow_mut().push(client.instance.clone());
+            let ret = match broken.contains(&client.instance.as_str()) {
+                true => Err(err()),
+                false => Ok(client.instance),
+            };
+            async move { ret }
+        }));
+        (ret, tried.into_inner())
+    }
+
+    #[test]
+    fn retries_next_instance() {
+        let pool = pool(&["a", "b", "c"]);
+        let (ret, tried) = run(&pool, &["a", "b"], unreachable);
+        let (answer, instance) = ret.unwrap();
+        assert_eq!(answer, "c");
+        assert_eq!(instance, "c");
+        assert_eq!(tried, ["a", "b", "c"]);
+
+        let answer = block_on(
+            pool.request(|client| async move { Ok(client.instance) }),
+        );
+        assert_eq!(answer.unwrap(), "a");
+    }
+
+    #[test]
+    fn gives_up() {
+        let pool = pool(&["a", "b", "c", "d"]);
+        let (ret, tried) = run(&pool, &["a", "b", "c", "d"], unreachable);
+        assert!(ret.unwrap_err().to_string().starts_with("c: "));
+        assert_eq!(tried.len(), MAX_ATTEMPTS);
+        assert!(run(&DewInstancePool::default(), &[], unreachable)
+            .0
+            .is_err());
+    }
+
+    #[test]
+    fn demotes_after_max_failures() {
+        let pool = pool(&["a", "b", "c"]);
+        for _ in 1..MAX_FAILURES {
+            run(&pool, &["a"], unreachable).0.unwrap();
+            assert_eq!(order(&pool), ["a", "b", "c"]);
+        }
+        run(&pool, &["a"], unreachable).0.unwrap();
+        assert_eq!(order(&pool), ["b", "c", "a"]);
+
+        // with a clean slate at the back.
+        let (_, tried) = run(&pool, &["a"], unreachable);
+        assert_eq!(tried, ["b"]);
+        assert_eq!(pool.instances.lock().unwrap()[2].failures, 0);
+    }
+
+    #[test]
+    fn success_resets_failures() {
+        let pool = pool(&["a", "b"]);
+        for _ in 1..MAX_FAILURES {
+            run(&pool, &["a"], unreachable).0.unwrap();
+        }
+        run(&pool, &[], unreachable).0.unwrap();
+        for _ in 1..MAX_FAILURES {
+            run(&pool, &["a"], unreachable).0.unwrap();
+        }
+        assert_eq!(order(&pool), ["a", "b"]);
+    }
+
+    #[test]
+    fn api_errors_dont_demote() {
+        let api_error = || InvidiousError::ApiError {
+            message: "video unavailable".into(),
+        };
+        let pool = pool(&["a", "b"]);
+        for _ in 0..=MAX_FAILURES {
+            let (ret, tried) = run(&pool, &["a"], api_error);
+            // still tried elsewhere.
+            assert_eq!(ret.unwrap().1, "b");
+            assert_eq!(tried, ["a", "b"]);
+        }
+        assert_eq!(order(&pool), ["a", "b"]);
+        assert_eq!(pool.instances.lock().unwrap()[0].failures, 0);
+    }
+}
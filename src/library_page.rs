@@ -0,0 +1,276 @@
+/* library_page.rs
+ *
+ * Copyright 2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[allow(unused_imports)]
+use adw::{prelude::*, subclass::prelude::*};
+use glib::{clone, g_warning, Variant};
+use gtk::{gio, glib};
+#[allow(unused_imports)]
+use gtk::{prelude::*, subclass::prelude::*};
+
+use serde::{Deserialize, Serialize};
+
+use crate::downloads;
+use crate::util;
+use crate::window::DewDuctWindow;
+use crate::yt_item_list::*;
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, gtk::CompositeTemplate)]
+    #[template(resource = "/null/daknig/DewDuct/library_page.ui")]
+    pub struct DewLibraryPage {
+        // Template widgets
+        #[template_child]
+        library_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        empty_page: TemplateChild<adw::StatusPage>,
+        #[template_child]
+        vid_list: TemplateChild<DewYtItemList>,
+        #[template_child]
+        row_menu: TemplateChild<gio::MenuModel>,
+
+        /// The downloaded file of each video in the list.
+        files: RefCell<HashMap<String, PathBuf>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DewLibraryPage {
+        const NAME: &'static str = "DewLibraryPage";
+        type Type = super::DewLibraryPage;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+            klass.install_action_async(
+                "library.play",
+                Some(glib::VariantTy::STRING),
+                Self::Type::play,
+            );
+            klass.install_action(
+                "library.delete",
+                Some(glib::VariantTy::STRING),
+                Self::Type::delete,
+            );
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for DewLibraryPage {
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.vid_list.set_row_menu(&*self.row_menu);
+            self.vid_list.set_activate_action("library.play");
+            self.vid_list.connect_items_changed(
+                clone!(@weak self as page => move |list| {
+                    if list.n_items() == 0 {
+                        page.library_stack
+                            .set_visible_child(&*page.empty_page);
+                    } else {
+                        page.library_stack
+                            .set_visible_child(&*page.vid_list);
+                    }
+                }),
+            );
+            glib::spawn_future_local(clone!(@weak self as page =>
+                 async move { page.load_state().await; }));
+        }
+    }
+    impl WidgetImpl for DewLibraryPage {}
+    impl BoxImpl for DewLibraryPage {}
+
+    #[gtk::template_callbacks]
+    impl DewLibraryPage {
+        fn window(&self) -> DewDuctWindow {
+            self.obj().root().and_downcast().unwrap()
+        }
+        fn store_state(&self) {
+            let path = util::data_path("library.json");
+            let files = self.files.borrow();
+            let library = Library {
+                entries: self
+                    .vid_list
+                    .get_vec()
+                    .into_iter()
+                    .filter_map(|item| {
+                        let file = files.get(&item.id())?.clone();
+                        Some(LibraryEntry {
+                            file,
+                            ..item.into()
+                        })
+                    })
+                    .collect(),
+            };
+            if let Err(err) = util::store_state(&path, &library) {
+                g_warning!("DewLibraryPage", "{:#}", err);
+            }
+        }
+        async fn load_state(&self) {
+            let path = util::data_path("library.json");
+            let library: Library = self
+                .window()
+                .spawn_blocking(move || util::load_state(&path))
+                .await
+                .unwrap_or_default();
+
+            // anything downloaded while loading is more recent.
+            let mut items: Vec<DewYtItem> =
+                self.vid_list.get_vec().into_iter().collect();
+            let mut files = self.files.borrow_mut();
+            for entry in library.entries {
+                if files.contains_key(&entry.id) {
+                    continue;
+                }
+                files.insert(entry.id.clone(), entry.file.clone());
+                items.push(entry.into());
+            }
+            drop(files);
+            self.vid_list.set_from_vec(items);
+        }
+        /// The downloaded file of the video, if it is still there.
+        pub(crate) fn file(&self, id: &str) -> Option<PathBuf> {
+            self.files
+                .borrow()
+                .get(id)
+                .filter(|file| file.exists())
+                .cloned()
+        }
+        /// Puts the video on top, replacing an older download of it.
+        pub(crate) fn add(&self, item: &DewYtItem, file: PathBuf) {
+            let id = item.id();
+            let mut items: Vec<DewYtItem> = self
+                .vid_list
+                .get_vec()
+                .into_iter()
+                .filter(|old| old.id() != id)
+                .collect();
+            items.insert(0, item.clone());
+            self.files.borrow_mut().insert(id, file);
+            self.vid_list.set_from_vec(items);
+            self.store_state();
+        }
+        pub(super) async fn play(&self, id: String) {
+            let Some(item) =
+                self.vid_list.get_vec().into_iter().find(|x| x.id() == id)
+            else {
+                return;
+            };
+            let window = self.window();
+            window.add_to_history(&item);
+            window.play_video(vec![(&item).into()]).await;
+        }
+        /// Takes it out of the library, and off the disk.
+        pub(super) fn delete(&self, id: String) {
+            if let Some(file) = self.files.borrow_mut().remove(&id) {
+                if let Err(err) = std::fs::remove_file(&file) {
+                    g_warning!(
+                        "DewLibraryPage",
+                        "can't delete {}: {}",
+                        file.display(),
+                        err
+                    );
+                }
+                downloads::remove_captions(&id);
+            }
+            self.vid_list.del_item_with_id(id);
+            self.store_state();
+        }
+        #[template_callback]
+        fn show_downloads(&self) {
+            self.window().show_downloads();
+        }
+    }
+
+    #[derive(Default, Deserialize, Serialize)]
+    pub(super) struct Library {
+        /// Most recently downloaded first.
+        entries: Vec<LibraryEntry>,
+    }
+
+    #[derive(Deserialize, Serialize)]
+    pub(super) struct LibraryEntry {
+        id: String,
+        title: String,
+        author: String,
+        thumbnails: Vec<Thumbnail>,
+        length: u64,
+        published: u64,
+        file: PathBuf,
+    }
+
+    impl From<DewYtItem> for LibraryEntry {
+        fn from(item: DewYtItem) -> Self {
+            Self {
+                id: item.id(),
+                title: item.title(),
+                author: item.author(),
+                thumbnails: item.thumbnails().to_vec(),
+                length: item.length(),
+                published: item.published(),
+                file: PathBuf::new(),
+            }
+        }
+    }
+    impl From<LibraryEntry> for DewYtItem {
+        fn from(entry: LibraryEntry) -> Self {
+            let ret: Self = glib::Object::builder()
+                .property("author", entry.author)
+                .property("id", entry.id)
+                .property("length", entry.length)
+                .property("published", entry.published)
+                .property("title", entry.title)
+                .build();
+            ret.set_thumbnails(entry.thumbnails);
+            ret
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct DewLibraryPage(ObjectSubclass<imp::DewLibraryPage>)
+        @extends gtk::Widget, gtk::Box,
+        @implements gio::ActionGroup, gio::ActionMap;
+}
+
+impl DewLibraryPage {
+    pub async fn play(self, _: String, param: Option<Variant>) {
+        let Some(id) = param.and_then(|param| param.get::<String>()) else {
+            g_warning!("DewLibraryPage", "library.play needs an id");
+            return;
+        };
+        self.imp().play(id).await;
+    }
+    pub fn delete(&self, _: &str, param: Option<&Variant>) {
+        let Some(id) = param.and_then(|param| param.get::<String>()) else {
+            g_warning!("DewLibraryPage", "library.delete needs an id");
+            return;
+        };
+        self.imp().delete(id);
+    }
+}
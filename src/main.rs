@@ -23,9 +23,31 @@ mod cache;
 mod channel_header;
 mod channel_page;
 mod channel_row;
+mod chapters;
+mod comment_row;
+mod comments_view;
 mod config;
+mod description;
+mod download_row;
+mod downloads;
+mod downloads_page;
+mod embedded_player;
+mod feed_page;
+mod history_page;
+mod instances;
+mod library_page;
+mod player;
+mod player_bar;
+mod playlist_page;
+mod playlist_row;
+mod playlists_page;
 mod popular_page;
+mod preferences;
+mod progress;
+mod quality;
+mod queue_page;
 mod search_page;
+mod settings;
 mod subscriptions_page;
 mod thumbnail;
 mod util;
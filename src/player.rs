@@ -0,0 +1,449 @@
+/* player.rs
+ *
+ * Copyright 2023-2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use glib::{g_debug, g_warning};
+use gtk::glib;
+
+use futures::channel::{mpsc, oneshot};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+/// How long mpv gets to open its IPC socket.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Error, Debug)]
+pub enum MpvError {
+    #[error("mpv is not installed")]
+    NotFound,
+    #[error("can't talk to mpv: {0}")]
+    Io(#[from] io::Error),
+    #[error("mpv did not open its socket in time")]
+    Timeout,
+    #[error("mpv refused the command: {0}")]
+    Refused(String),
+    #[error("mpv is gone")]
+    Disconnected,
+}
+
+/// Something mpv reported without being asked.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MpvEvent {
+    /// A property registered with `observe` changed.
+    PropertyChange {
+        name: String,
+        data: Value,
+    },
+    /// Playback stopped; `reason` is `eof` when the video is over.
+    EndFile {
+        reason: String,
+    },
+    Other(String),
+}
+
+impl MpvEvent {
+    fn from_json(mut msg: Value) -> Option<Self> {
+        let event = msg.get("event")?.as_str()?.to_owned();
+        Some(match event.as_str() {
+            "property-change" => Self::PropertyChange {
+                name: msg.get("name")?.as_str()?.to_owned(),
+                data: msg
+                    .get_mut("data")
+                    .map(Value::take)
+                    .unwrap_or_default(),
+            },
+            "end-file" => Self::EndFile {
+                reason: msg
+                    .get("reason")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_owned(),
+            },
+            _ => Self::Other(event),
+        })
+    }
+}
+
+type Pending =
+    Arc<Mutex<HashMap<u64, oneshot::Sender<Result<Value, MpvError>>>>>;
+
+/// The JSON IPC protocol of mpv, over a connected socket. Replies are
+/// matched to their commands by `request_id`; everything else is an event.
+pub struct MpvIpc {
+    stream: UnixStream,
+    next_request_id: AtomicU64,
+    pending: Pending,
+    events: Mutex<Option<mpsc::UnboundedReceiver<MpvEvent>>>,
+}
+
+impl MpvIpc {
+    pub fn new(stream: UnixStream) -> io::Result<Self> {
+        let pending: Pending = Default::default();
+        let (events_tx, events_rx) = mpsc::unbounded();
+
+        let reader = BufReader::new(stream.try_clone()?);
+        let pending_clone = pending.clone();
+        thread::spawn(move || {
+            Self::read_loop(reader, pending_clone, events_tx)
+        });
+
+        Ok(Self {
+            stream,
+            next_request_id: AtomicU64::new(1),
+            pending,
+            events: Mutex::new(Some(events_rx)),
+        })
+    }
+
+    fn read_loop(
+        reader: BufReader<UnixStream>,
+        pending: Pending,
+        events: mpsc::UnboundedSender<MpvEvent>,
+    ) {
+        for line in reader.lines() {
+            let Ok(line) = line else { break };
+            let Ok(msg) = serde_json::from_str::<Value>(&line) else {
+                g_debug!("DewPlayer", "unknown message from mpv: {}", line);
+                continue;
+            };
+
+            if let Some(request_id) =
+                msg.get("request_id").and_then(Value::as_u64)
+            {
+                let reply = match msg.get("error").and_then(Value::as_str) {
+                    Some("success") => {
+                        Ok(msg.get("data").cloned().unwrap_or_default())
+                    }
+                    Some(err) => Err(MpvError::Refused(err.to_owned())),
+                    None => Err(MpvError::Refused(line.clone())),
+                };
+                let sender = pending.lock().unwrap().remove(&request_id);
+                if let Some(sender) = sender {
+                    let _ = sender.send(reply);
+                }
+            } else if let Some(event) = MpvEvent::from_json(msg) {
+                // nobody listening is fine.
+                let _ = events.unbounded_send(event);
+            }
+        }
+        // dropping the senders wakes everyone still waiting.
+        pending.lock().unwrap().clear();
+    }
+
+    /// The events, in order. Can only be taken once.
+    pub fn events(&self) -> Option<mpsc::UnboundedReceiver<MpvEvent>> {
+        self.events.lock().unwrap().take()
+    }
+
+    pub async fn command(&self, args: Value) -> Result<Value, MpvError> {
+        let request_id =
+            self.next_request_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(request_id, tx);
+
+        let mut line = json!({ "command": args, "request_id": request_id })
+            .to_string();
+        line.push('\n');
+        if let Err(err) = (&self.stream).write_all(line.as_bytes()) {
+            self.pending.lock().unwrap().remove(&request_id);
+            return Err(err.into());
+        }
+
+        rx.await.unwrap_or(Err(MpvError::Disconnected))
+    }
+
+    // the app follows properties with `observe`; these are for asking
+    // once.
+    #[allow(dead_code)]
+    pub async fn get_property(
+        &self,
+        name: &str,
+    ) -> Result<Value, MpvError> {
+        self.command(json!(["get_property", name])).await
+    }
+
+    pub async fn set_property(
+        &self,
+        name: &str,
+        value: Value,
+    ) -> Result<(), MpvError> {
+        self.command(json!(["set_property", name, value])).await?;
+        Ok(())
+    }
+
+    /// Changes of the property come as `MpvEvent::PropertyChange`.
+    pub async fn observe(&self, name: &str) -> Result<(), MpvError> {
+        let id = self.next_request_id.fetch_add(1, Ordering::Relaxed);
+        self.command(json!(["observe_property", id, name])).await?;
+        Ok(())
+    }
+
+    pub async fn set_pause(&self, pause: bool) -> Result<(), MpvError> {
+        self.set_property("pause", json!(pause)).await
+    }
+
+    /// To an absolute position, in seconds.
+    pub async fn seek(&self, position: f64) -> Result<(), MpvError> {
+        self.command(json!(["seek", position, "absolute"])).await?;
+        Ok(())
+    }
+
+    /// In seconds; `None` while nothing is playing.
+    #[allow(dead_code)]
+    pub async fn position(&self) -> Result<Option<f64>, MpvError> {
+        Ok(self.get_property("time-pos").await?.as_f64())
+    }
+}
+
+/// A new socket for every mpv, so an old one going away does not bother
+/// the new one.
+fn ipc_socket_path() -> PathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    glib::user_runtime_dir().join("DewDuct/").join(format!(
+        "mpv-{}-{}.sock",
+        std::process::id(),
+        n
+    ))
+}
+
+/// An mpv process, controlled over its JSON IPC socket. It is killed when
+/// dropped.
+pub struct DewMpv {
+    child: Child,
+    socket: PathBuf,
+    ipc: MpvIpc,
+}
+
+impl DewMpv {
+    /// Runs `mpv`, which should have everything but the IPC options set.
+    pub async fn spawn(mut mpv: Command) -> Result<Self, MpvError> {
+        let socket = ipc_socket_path();
+        if let Some(parent) = socket.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        mpv.arg(format!("--input-ipc-server={}", socket.display()));
+        g_debug!(
+            "DewPlayer",
+            "running... {:?} {:?}",
+            mpv.get_program(),
+            mpv.get_args().collect::<Vec<_>>()
+        );
+        let mut child = mpv.spawn().map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => MpvError::NotFound,
+            _ => err.into(),
+        })?;
+
+        match Self::connect(&socket, &mut child).await {
+            Ok(stream) => Ok(Self {
+                child,
+                ipc: MpvIpc::new(stream)?,
+                socket,
+            }),
+            Err(err) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                Err(err)
+            }
+        }
+    }
+
+    /// mpv creates the socket a moment after it starts.
+    async fn connect(
+        socket: &Path,
+        child: &mut Child,
+    ) -> Result<UnixStream, MpvError> {
+        let start = Instant::now();
+        loop {
+            match UnixStream::connect(socket) {
+                Ok(stream) => return Ok(stream),
+                Err(_) if start.elapsed() > CONNECT_TIMEOUT => {
+                    return Err(MpvError::Timeout)
+                }
+                Err(_) => {}
+            }
+            if !matches!(child.try_wait(), Ok(None)) {
+                return Err(MpvError::Disconnected);
+            }
+            glib::timeout_future(Duration::from_millis(100)).await;
+        }
+    }
+}
+
+impl std::fmt::Debug for DewMpv {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DewMpv")
+            .field("pid", &self.child.id())
+            .field("socket", &self.socket)
+            .finish_non_exhaustive()
+    }
+}
+
+impl std::ops::Deref for DewMpv {
+    type Target = MpvIpc;
+    fn deref(&self) -> &MpvIpc {
+        &self.ipc
+    }
+}
+
+impl Drop for DewMpv {
+    fn drop(&mut self) {
+        if let Err(err) = self.child.kill() {
+            g_warning!("DewPlayer", "can't stop mpv: {}", err);
+        }
+        let _ = self.child.wait();
+        let _ = fs::remove_file(&self.socket);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use futures::StreamExt;
+
+    /// `MpvIpc` on one end, and on the other end a fake mpv that reads a
+    /// single command, answers with `reply` if any, and hangs up.
+    fn fake_mpv(
+        reply: Option<&'static str>,
+    ) -> (MpvIpc, thread::JoinHandle<Value>) {
+        let (ours, theirs) = UnixStream::pair().unwrap();
+        let peer = thread::spawn(move || {
+            let mut reader = BufReader::new(theirs.try_clone().unwrap());
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let command: Value = serde_json::from_str(&line).unwrap();
+
+            if let Some(reply) = reply {
+                let mut reply: Value = serde_json::from_str(reply).unwrap();
+                reply["request_id"] = command["request_id"].clone();
+                writeln!(&theirs, "{reply}").unwrap();
+            }
+            command
+        });
+        (MpvIpc::new(ours).unwrap(), peer)
+    }
+
+    #[test]
+    fn query_position() {
+        let (ipc, peer) =
+            fake_mpv(Some(r#"{"error": "success", "data": 12.5}"#));
+        assert_eq!(block_on(ipc.position()).unwrap(), Some(12.5));
+        assert_eq!(
+            peer.join().unwrap()["command"],
+            json!(["get_property", "time-pos"])
+        );
+    }
+
+    #[test]
+    fn nothing_playing() {
+        let (ipc, _peer) =
+            fake_mpv(Some(r#"{"error": "success", "data": null}"#));
+        assert_eq!(block_on(ipc.position()).unwrap(), None);
+    }
+
+    #[test]
+    fn query_property() {
+        let (ipc, peer) =
+            fake_mpv(Some(r#"{"error": "success", "data": "yes"}"#));
+        assert_eq!(
+            block_on(ipc.get_property("pause")).unwrap(),
+            json!("yes")
+        );
+        assert_eq!(
+            peer.join().unwrap()["command"],
+            json!(["get_property", "pause"])
+        );
+    }
+
+    #[test]
+    fn pause_and_seek() {
+        let (ipc, peer) = fake_mpv(Some(r#"{"error": "success"}"#));
+        block_on(ipc.set_pause(true)).unwrap();
+        assert_eq!(
+            peer.join().unwrap()["command"],
+            json!(["set_property", "pause", true])
+        );
+
+        let (ipc, peer) = fake_mpv(Some(r#"{"error": "success"}"#));
+        block_on(ipc.seek(90.)).unwrap();
+        assert_eq!(
+            peer.join().unwrap()["command"],
+            json!(["seek", 90., "absolute"])
+        );
+    }
+
+    #[test]
+    fn refused_command() {
+        let (ipc, _peer) =
+            fake_mpv(Some(r#"{"error": "property unavailable"}"#));
+        let err = block_on(ipc.position()).unwrap_err();
+        assert!(
+            matches!(err, MpvError::Refused(msg) if msg == "property unavailable")
+        );
+    }
+
+    #[test]
+    fn mpv_going_away() {
+        let (ipc, _peer) = fake_mpv(None);
+        let err = block_on(ipc.position()).unwrap_err();
+        assert!(matches!(err, MpvError::Disconnected));
+    }
+
+    #[test]
+    fn events() {
+        let (ours, theirs) = UnixStream::pair().unwrap();
+        let ipc = MpvIpc::new(ours).unwrap();
+        writeln!(
+            &theirs,
+            r#"{{"event": "property-change", "id": 1, "name": "time-pos", "data": 3.0}}"#
+        )
+        .unwrap();
+        writeln!(&theirs, r#"{{"event": "end-file", "reason": "eof"}}"#)
+            .unwrap();
+        drop(theirs);
+
+        let events: Vec<MpvEvent> =
+            block_on(ipc.events().unwrap().collect());
+        assert_eq!(
+            events,
+            [
+                MpvEvent::PropertyChange {
+                    name: "time-pos".into(),
+                    data: json!(3.0),
+                },
+                MpvEvent::EndFile {
+                    reason: "eof".into()
+                },
+            ]
+        );
+        assert!(ipc.events().is_none());
+    }
+}
@@ -0,0 +1,447 @@
+/* player_bar.rs
+ *
+ * Copyright 2023-2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::{Cell, RefCell};
+use std::fmt::Display;
+use std::process::Command;
+use std::rc::{Rc, Weak};
+
+#[allow(unused_imports)]
+use adw::{prelude::*, subclass::prelude::*};
+use glib::{clone, g_warning};
+use gtk::glib;
+#[allow(unused_imports)]
+use gtk::{prelude::*, subclass::prelude::*};
+
+use anyhow::Context;
+use futures::{channel::mpsc, StreamExt};
+use invidious::ClientAsyncTrait;
+use serde_json::json;
+
+use crate::player::{DewMpv, MpvEvent};
+use crate::progress::ProgressTracker;
+use crate::quality::{self, best_audio_url, Quality};
+use crate::settings::PlaybackSource;
+use crate::util::progress;
+use crate::window::DewDuctWindow;
+use crate::yt_item_list::DewYtItem;
+
+/// Something to listen to.
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub id: String,
+    pub title: String,
+    /// A VTT file to show along with it.
+    pub captions: Option<String>,
+}
+
+impl From<&DewYtItem> for Track {
+    fn from(item: &DewYtItem) -> Self {
+        Self {
+            id: item.id(),
+            title: item.title(),
+            captions: None,
+        }
+    }
+}
+
+/// How the tracks are played.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BarMode {
+    /// Without video, in the background.
+    #[default]
+    Audio,
+    /// In a small mpv window, on top of the others.
+    Popup,
+    /// In a regular mpv window.
+    Video,
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, gtk::CompositeTemplate)]
+    #[template(resource = "/null/daknig/DewDuct/player_bar.ui")]
+    pub struct DewPlayerBar {
+        // Template widgets
+        #[template_child]
+        mode_icon: TemplateChild<gtk::Image>,
+        #[template_child]
+        title: TemplateChild<gtk::Label>,
+        #[template_child]
+        pause_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        next_button: TemplateChild<gtk::Button>,
+
+        mpv: RefCell<Option<Rc<DewMpv>>>,
+        mode: Cell<BarMode>,
+        tracks: RefCell<Vec<Track>>,
+        /// In `tracks`, of the one playing.
+        position: Cell<usize>,
+        paused: Cell<bool>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DewPlayerBar {
+        const NAME: &'static str = "DewPlayerBar";
+        type Type = super::DewPlayerBar;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for DewPlayerBar {}
+    impl WidgetImpl for DewPlayerBar {}
+    impl BoxImpl for DewPlayerBar {}
+
+    #[gtk::template_callbacks]
+    impl DewPlayerBar {
+        fn window(&self) -> DewDuctWindow {
+            self.obj().root().and_downcast().unwrap()
+        }
+        fn mpv(&self) -> Option<Rc<DewMpv>> {
+            self.mpv.borrow().clone()
+        }
+        fn is_current(&self, mpv: &Weak<DewMpv>) -> bool {
+            self.mpv.borrow().as_ref().is_some_and(|current| {
+                Weak::ptr_eq(&Rc::downgrade(current), mpv)
+            })
+        }
+        fn error(&self, err: impl Display) {
+            g_warning!("DewPlayerBar", "{}", err);
+            self.window().toast(&err.to_string());
+        }
+        #[template_callback]
+        async fn pause_clicked(&self) {
+            let Some(mpv) = self.mpv() else { return };
+            if let Err(err) = mpv.set_pause(!self.paused.get()).await {
+                self.error(err);
+            }
+        }
+        #[template_callback]
+        async fn next_clicked(&self) {
+            let Some(mpv) = self.mpv() else { return };
+            if self.position.get() + 1 >= self.tracks.borrow().len() {
+                // the last one; on to the queue.
+                let mode = self.mode.get();
+                self.stop();
+                return self.window().play_queue(mode).await;
+            }
+            if let Err(err) = mpv.command(json!(["playlist-next"])).await {
+                self.error(err);
+            }
+        }
+        /// There is a next one in the list, or in the queue.
+        pub(super) fn update_next(&self) {
+            let has_next = self.position.get() + 1
+                < self.tracks.borrow().len()
+                || !self.window().queue_is_empty();
+            self.next_button.set_sensitive(has_next);
+        }
+        pub(super) fn is_playing(&self) -> bool {
+            self.mpv.borrow().is_some()
+        }
+        /// mpv is stopped once dropped.
+        #[template_callback]
+        pub(super) fn stop(&self) {
+            self.mpv.take();
+            self.tracks.take();
+            self.position.set(0);
+            self.obj().set_visible(false);
+        }
+
+        /// What mpv should open to play `id` in the current mode.
+        async fn stream_url(&self, id: &str) -> anyhow::Result<String> {
+            let window = self.window();
+            if let Some(file) = window.downloaded_file(id) {
+                return Ok(file.to_string_lossy().into_owned());
+            }
+            let source = window.settings().playback_source;
+            if source == PlaybackSource::Ytdl {
+                return Ok(format!("https://youtube.com/watch?v={id}"));
+            }
+
+            let params =
+                (source == PlaybackSource::Proxied).then_some("local=true");
+            let pool = window.instance_pool();
+            let id = id.to_owned();
+            let (vid, instance) = window
+                .spawn(async move {
+                    pool.request_from(|invidious| {
+                        let id = id.clone();
+                        async move { invidious.video(&id, params).await }
+                    })
+                    .await
+                })
+                .await??;
+            if self.mode.get() == BarMode::Audio {
+                return best_audio_url(&vid, &instance).with_context(
+                    || format!("no audio stream for {}", vid.id),
+                );
+            }
+
+            // one url per file, so only streams with the audio in them.
+            let muxed: Vec<Quality> = quality::qualities(&vid, &instance)
+                .into_iter()
+                .filter(|quality| quality.urls.len() == 1)
+                .collect();
+            let preferred = quality::preferred(
+                &muxed,
+                window.settings().default_quality,
+            );
+            match muxed.into_iter().nth(preferred) {
+                Some(quality) => Ok(quality.urls.concat()),
+                None if !vid.dash.is_empty() => {
+                    Ok(quality::resolve_url(&vid.dash, &instance))
+                }
+                None => anyhow::bail!("no stream for {}", vid.id),
+            }
+        }
+
+        pub(super) async fn play(&self, tracks: Vec<Track>, mode: BarMode) {
+            self.stop();
+            let Some(first) = tracks.first() else { return };
+            self.mode.set(mode);
+            self.mode_icon.set_icon_name(Some(match mode {
+                BarMode::Audio => "audio-headphones-symbolic",
+                BarMode::Popup => "send-to-symbolic",
+                BarMode::Video => "video-display-symbolic",
+            }));
+            let url = match self.stream_url(&first.id).await {
+                Ok(url) => url,
+                Err(err) => return self.error(format!("{err:#}")),
+            };
+
+            let settings = self.window().settings();
+            let mut mpv = Command::new("mpv");
+            let ytdl_format = match mode {
+                BarMode::Audio => {
+                    mpv.arg("--no-video").arg("--force-window=no");
+                    "bestaudio/best".into()
+                }
+                BarMode::Popup => {
+                    // small, in the bottom right corner.
+                    mpv.args([
+                        "--ontop",
+                        "--autofit=25%",
+                        "--geometry=-24-48",
+                    ]);
+                    quality::fallback_ytdl_format(settings.default_quality)
+                }
+                BarMode::Video => {
+                    quality::fallback_ytdl_format(settings.default_quality)
+                }
+            };
+            match settings.playback_source {
+                PlaybackSource::Ytdl => {
+                    mpv.arg(format!("--ytdl-format={ytdl_format}"))
+                }
+                _ => mpv.arg("--ytdl=no"),
+            };
+            mpv.arg(url);
+
+            let mpv = match DewMpv::spawn(mpv).await {
+                Ok(mpv) => Rc::new(mpv),
+                Err(err) => return self.error(err),
+            };
+            for property in
+                ["playlist-pos", "pause", "time-pos", "duration"]
+            {
+                if let Err(err) = mpv.observe(property).await {
+                    g_warning!(
+                        "DewPlayerBar",
+                        "can't observe mpv: {}",
+                        err
+                    );
+                }
+            }
+            let events = mpv.events();
+            let weak_mpv = Rc::downgrade(&mpv);
+            self.mpv.replace(Some(mpv));
+            self.tracks.replace(tracks.clone());
+            self.obj().set_visible(true);
+
+            // the rest is resolved while the first one plays.
+            glib::spawn_future_local(clone!(@weak self as bar,
+                @strong weak_mpv => async move {
+                    for track in tracks.iter().skip(1) {
+                        let url = match bar.stream_url(&track.id).await {
+                            Ok(url) => url,
+                            Err(err) => {
+                                g_warning!("DewPlayerBar", "{:#}", err);
+                                continue;
+                            }
+                        };
+                        let Some(mpv) = weak_mpv.upgrade() else { break };
+                        if !bar.is_current(&weak_mpv) {
+                            break;
+                        }
+                        let loadfile = json!(["loadfile", url, "append"]);
+                        if let Err(err) = mpv.command(loadfile).await {
+                            g_warning!("DewPlayerBar", "{}", err);
+                        }
+                    }
+                }
+            ));
+
+            if let Some(events) = events {
+                self.follow(weak_mpv, events).await;
+            }
+        }
+
+        /// Until mpv is done with the whole list.
+        async fn follow(
+            &self,
+            mpv: Weak<DewMpv>,
+            mut events: mpsc::UnboundedReceiver<MpvEvent>,
+        ) {
+            let mut tracker: Option<ProgressTracker> = None;
+            // seeking only works once the file is loaded, which is when
+            // its duration is known.
+            let mut resume: Option<f64> = None;
+            // same for adding captions.
+            let mut captions: Option<String> = None;
+            let mut finished = false;
+            while let Some(event) = events.next().await {
+                let (name, data) = match event {
+                    MpvEvent::PropertyChange { name, data } => (name, data),
+                    MpvEvent::EndFile { reason } => {
+                        finished = reason == "eof";
+                        continue;
+                    }
+                    MpvEvent::Other(_) => continue,
+                };
+                match name.as_str() {
+                    "playlist-pos" => {
+                        let Some(pos) = data.as_u64() else { continue };
+                        let pos = pos as usize;
+                        let tracks = self.tracks.borrow();
+                        let track = tracks.get(pos);
+                        self.title.set_text(
+                            track.map_or("", |track| track.title.as_str()),
+                        );
+                        self.position.set(pos);
+                        tracker = track.map(|track| {
+                            ProgressTracker::new(track.id.clone())
+                        });
+                        resume = track
+                            .and_then(|track| progress().get(&track.id))
+                            .and_then(|watched| watched.resume_position());
+                        captions = track
+                            .and_then(|track| track.captions.clone())
+                            .filter(|_| self.mode.get() != BarMode::Audio);
+                        drop(tracks);
+                        self.update_next();
+                    }
+                    "pause" => {
+                        let paused = data.as_bool().unwrap_or_default();
+                        self.paused.set(paused);
+                        let (icon, tooltip) = match paused {
+                            true => {
+                                ("media-playback-start-symbolic", "Play")
+                            }
+                            false => {
+                                ("media-playback-pause-symbolic", "Pause")
+                            }
+                        };
+                        self.pause_button.set_icon_name(icon);
+                        self.pause_button.set_tooltip_text(Some(tooltip));
+                    }
+                    name => {
+                        if let (Some(tracker), Some(data)) =
+                            (tracker.as_mut(), data.as_f64())
+                        {
+                            tracker.update(name, data);
+                        }
+                        if name != "duration" || data.is_null() {
+                            continue;
+                        }
+                        let Some(player) = mpv.upgrade() else {
+                            continue;
+                        };
+                        if let Some(url) = captions.take() {
+                            let sub_add = json!(["sub-add", url, "select"]);
+                            if let Err(err) = player.command(sub_add).await
+                            {
+                                g_warning!(
+                                    "DewPlayerBar",
+                                    "can't add the captions: {}",
+                                    err
+                                );
+                            }
+                        }
+                        let Some(start) = resume.take() else {
+                            continue;
+                        };
+                        if let Err(err) = player.seek(start).await {
+                            g_warning!(
+                                "DewPlayerBar",
+                                "can't resume: {}",
+                                err
+                            );
+                        }
+                    }
+                }
+            }
+            if !self.is_current(&mpv) {
+                return;
+            }
+            let mode = self.mode.get();
+            self.stop();
+            // played to the end rather than closed, so the queue goes on.
+            if finished {
+                let window = self.window();
+                glib::spawn_future_local(async move {
+                    window.play_queue(mode).await
+                });
+            }
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct DewPlayerBar(ObjectSubclass<imp::DewPlayerBar>)
+        @extends gtk::Widget, gtk::Box,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl DewPlayerBar {
+    /// Plays the tracks one after the other, each from where the user
+    /// stopped, replacing whatever was playing.
+    pub async fn play(&self, tracks: Vec<Track>, mode: BarMode) {
+        self.imp().play(tracks, mode).await
+    }
+    pub fn stop(&self) {
+        self.imp().stop()
+    }
+    pub fn is_playing(&self) -> bool {
+        self.imp().is_playing()
+    }
+    pub fn update_next(&self) {
+        self.imp().update_next()
+    }
+}
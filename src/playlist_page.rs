@@ -0,0 +1,247 @@
+/* playlist_page.rs
+ *
+ * Copyright 2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::RefCell;
+
+#[allow(unused_imports)]
+use adw::{prelude::*, subclass::prelude::*};
+use glib::{clone, g_warning, Variant};
+use gtk::{gio, glib};
+#[allow(unused_imports)]
+use gtk::{prelude::*, subclass::prelude::*};
+
+use crate::player_bar::BarMode;
+use crate::playlists_page::{ask_playlist_name, LocalPlaylist};
+use crate::window::DewDuctWindow;
+use crate::yt_item_list::*;
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, gtk::CompositeTemplate)]
+    #[template(resource = "/null/daknig/DewDuct/playlist_page.ui")]
+    pub struct DewPlaylistPage {
+        // Template widgets
+        #[template_child]
+        window_title: TemplateChild<adw::WindowTitle>,
+        #[template_child]
+        menu_button: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        play_all_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        playlist_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        empty_page: TemplateChild<adw::StatusPage>,
+        #[template_child]
+        vid_list: TemplateChild<DewYtItemList>,
+        #[template_child]
+        row_menu: TemplateChild<gio::MenuModel>,
+
+        /// Of the local playlist shown.
+        local_id: RefCell<Option<String>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DewPlaylistPage {
+        const NAME: &'static str = "DewPlaylistPage";
+        type Type = super::DewPlaylistPage;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+            klass.install_action_async(
+                "playlist.rename",
+                None,
+                |page, _, _| async move { page.imp().rename().await },
+            );
+            klass.install_action_async(
+                "playlist.delete",
+                None,
+                |page, _, _| async move { page.imp().delete().await },
+            );
+            klass.install_action(
+                "playlist.remove",
+                Some(glib::VariantTy::STRING),
+                Self::Type::remove,
+            );
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for DewPlaylistPage {
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.vid_list.connect_items_changed(
+                clone!(@weak self as page => move |list| {
+                    let n_items = list.n_items();
+                    page.play_all_button.set_sensitive(n_items > 0);
+                    page.window_title.set_subtitle(&match n_items {
+                        1 => "1 video".to_owned(),
+                        n => format!("{n} videos"),
+                    });
+                    if n_items == 0 {
+                        page.playlist_stack
+                            .set_visible_child(&*page.empty_page);
+                    } else {
+                        page.playlist_stack
+                            .set_visible_child(&*page.vid_list);
+                    }
+                }),
+            );
+        }
+    }
+    impl WidgetImpl for DewPlaylistPage {}
+    impl BoxImpl for DewPlaylistPage {}
+
+    #[gtk::template_callbacks]
+    impl DewPlaylistPage {
+        fn window(&self) -> DewDuctWindow {
+            self.obj().root().and_downcast().unwrap()
+        }
+        pub(super) fn show_local(&self, playlist: LocalPlaylist) {
+            self.window_title.set_title(&playlist.name);
+            self.menu_button.set_visible(true);
+            self.empty_page.set_description(Some(
+                "Add videos to this playlist from their menu.",
+            ));
+            self.vid_list.set_row_menu(&*self.row_menu);
+            self.vid_list.set_from_vec(
+                playlist.videos.into_iter().map(DewYtItem::from).collect(),
+            );
+            self.local_id.replace(Some(playlist.id));
+        }
+        /// Nothing can be changed in a playlist from the instance.
+        pub(super) fn show_remote(
+            &self,
+            title: &str,
+            author: &str,
+            videos: Vec<DewYtItem>,
+        ) {
+            self.window_title.set_title(&format!("{title} · {author}"));
+            self.menu_button.set_visible(false);
+            self.empty_page
+                .set_description(Some("This playlist has no videos."));
+            self.vid_list.set_row_menu(&gio::Menu::new());
+            self.vid_list.set_from_vec(videos);
+            self.local_id.take();
+        }
+        #[template_callback]
+        async fn play_all(&self) {
+            let window = self.window();
+            let items: Vec<DewYtItem> =
+                self.vid_list.get_vec().into_iter().collect();
+            window.enqueue(&items);
+            window.start_queue(BarMode::Video).await;
+        }
+        async fn rename(&self) {
+            let Some(id) = self.local_id.borrow().clone() else {
+                return;
+            };
+            let window = self.window();
+            let name = self.window_title.title();
+            let Some(name) = ask_playlist_name(
+                &window,
+                "Rename Playlist",
+                &name,
+                "_Rename",
+            )
+            .await
+            else {
+                return;
+            };
+            self.window_title.set_title(&name);
+            window.rename_playlist(&id, name);
+        }
+        async fn delete(&self) {
+            let Some(id) = self.local_id.borrow().clone() else {
+                return;
+            };
+            let window = self.window();
+            let dialog = adw::MessageDialog::new(
+                Some(&window),
+                Some("Delete Playlist?"),
+                Some(&format!(
+                    "“{}” will be gone for good.",
+                    self.window_title.title()
+                )),
+            );
+            dialog.add_responses(&[
+                ("cancel", "_Cancel"),
+                ("delete", "_Delete"),
+            ]);
+            dialog.set_response_appearance(
+                "delete",
+                adw::ResponseAppearance::Destructive,
+            );
+            dialog.set_default_response(Some("cancel"));
+            dialog.set_close_response("cancel");
+
+            if dialog.choose_future().await.as_str() == "delete" {
+                window.delete_playlist(&id);
+                self.local_id.take();
+                if let Some(nav_view) = self
+                    .obj()
+                    .ancestor(adw::NavigationView::static_type())
+                    .and_downcast::<adw::NavigationView>()
+                {
+                    nav_view.pop();
+                }
+            }
+        }
+        pub(super) fn remove(&self, vid_id: String) {
+            let Some(id) = self.local_id.borrow().clone() else {
+                return;
+            };
+            self.window().remove_from_playlist(&id, &vid_id);
+            self.vid_list.del_item_with_id(vid_id);
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct DewPlaylistPage(ObjectSubclass<imp::DewPlaylistPage>)
+        @extends gtk::Widget, gtk::Box,
+        @implements gio::ActionGroup, gio::ActionMap;
+}
+
+impl DewPlaylistPage {
+    pub fn show_local(&self, playlist: LocalPlaylist) {
+        self.imp().show_local(playlist)
+    }
+    pub fn show_remote(
+        &self,
+        title: &str,
+        author: &str,
+        videos: Vec<DewYtItem>,
+    ) {
+        self.imp().show_remote(title, author, videos)
+    }
+    pub fn remove(&self, _: &str, param: Option<&Variant>) {
+        let Some(id) = param.and_then(|param| param.get::<String>()) else {
+            g_warning!("DewPlaylistPage", "playlist.remove needs an id");
+            return;
+        };
+        self.imp().remove(id);
+    }
+}
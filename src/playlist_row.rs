@@ -0,0 +1,138 @@
+/* playlist_row.rs
+ *
+ * Copyright 2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::path::Path;
+
+#[allow(unused_imports)]
+use adw::{prelude::*, subclass::prelude::*};
+use glib::g_warning;
+use gtk::{gdk, glib};
+#[allow(unused_imports)]
+use gtk::{prelude::*, subclass::prelude::*};
+
+use crate::cache::DewCache;
+use crate::yt_item_list::Thumbnail;
+use crate::{cache, cache_dir};
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, gtk::CompositeTemplate)]
+    #[template(resource = "/null/daknig/DewDuct/playlist_row.ui")]
+    pub struct DewPlaylistRow {
+        #[template_child]
+        pub(super) thumbnail: TemplateChild<gtk::Picture>,
+        #[template_child]
+        pub(super) video_count: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub(super) title: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub(super) author: TemplateChild<gtk::Label>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DewPlaylistRow {
+        const NAME: &'static str = "DewPlaylistRow";
+        type Type = super::DewPlaylistRow;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for DewPlaylistRow {
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.thumbnail.set_resource(Some(
+                "/null/daknig/DewDuct/dummi_thumbnail.svg",
+            ));
+        }
+    }
+    impl WidgetImpl for DewPlaylistRow {}
+    impl BoxImpl for DewPlaylistRow {}
+}
+
+glib::wrapper! {
+    pub struct DewPlaylistRow(ObjectSubclass<imp::DewPlaylistRow>)
+        @extends gtk::Widget, gtk::Box,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl DewPlaylistRow {
+    pub async fn set_from_params(
+        &self,
+        title: String,
+        author: String,
+        video_count: u32,
+        thumbnails: &[Thumbnail],
+        id: String,
+    ) -> anyhow::Result<()> {
+        self.imp().title.set_text(&title);
+        self.imp().author.set_text(&author);
+        self.imp().video_count.set_text(&match video_count {
+            1 => "1 video".to_owned(),
+            n => format!("{n} videos"),
+        });
+
+        let thumb = thumbnails
+            .iter()
+            .filter(|thumb| thumb.width >= 320)
+            .min_by_key(|thumb| thumb.width)
+            .or(thumbnails.iter().max_by_key(|thumb| thumb.width))
+            .ok_or(Err::NoThumbnails { id: id.clone() })?;
+
+        let mut thumbnail_fname = cache_dir(Path::new(&id));
+        thumbnail_fname.push(thumb.height.to_string());
+        thumbnail_fname.set_extension("jpg");
+
+        match DewCache::fetch_remote(
+            cache(),
+            thumbnail_fname.clone(),
+            &thumb.url,
+        )
+        .await
+        {
+            Ok(data) => {
+                let content_bytes = glib::Bytes::from_owned(data);
+                let paintable = gdk::Texture::from_bytes(&content_bytes)?;
+                self.imp().thumbnail.set_paintable(Some(&paintable));
+            }
+            Err(err) => g_warning!(
+                "DewPlaylistRow",
+                "could not fetch file {}: {err}",
+                thumbnail_fname.display()
+            ),
+        }
+
+        Ok(())
+    }
+}
+
+use thiserror::Error;
+#[derive(Error, Debug)]
+pub enum Err {
+    #[error("no thumbnails found for playlist ID {id}")]
+    NoThumbnails { id: String },
+}
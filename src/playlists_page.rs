@@ -0,0 +1,331 @@
+/* playlists_page.rs
+ *
+ * Copyright 2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[allow(unused_imports)]
+use adw::{prelude::*, subclass::prelude::*};
+use glib::{clone, g_warning};
+use gtk::{gio, glib};
+#[allow(unused_imports)]
+use gtk::{prelude::*, subclass::prelude::*};
+
+use serde::{Deserialize, Serialize};
+
+use crate::util;
+use crate::window::DewDuctWindow;
+use crate::yt_item_list::*;
+
+/// A playlist of the user, kept on this machine.
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct LocalPlaylist {
+    pub id: String,
+    pub name: String,
+    pub videos: Vec<DewYtItemSnapshot>,
+}
+
+/// Asks for the name of a playlist; `None` if the user cancelled.
+pub async fn ask_playlist_name(
+    window: &DewDuctWindow,
+    heading: &str,
+    name: &str,
+    confirm: &str,
+) -> Option<String> {
+    let dialog = adw::MessageDialog::new(Some(window), Some(heading), None);
+    let entry = gtk::Entry::builder()
+        .text(name)
+        .placeholder_text("Name")
+        .activates_default(true)
+        .build();
+    dialog.set_extra_child(Some(&entry));
+    dialog.add_responses(&[("cancel", "_Cancel"), ("confirm", confirm)]);
+    dialog.set_response_appearance(
+        "confirm",
+        adw::ResponseAppearance::Suggested,
+    );
+    dialog.set_default_response(Some("confirm"));
+    dialog.set_close_response("cancel");
+    entry.connect_changed(clone!(@weak dialog => move |entry| {
+        dialog.set_response_enabled(
+            "confirm",
+            !entry.text().trim().is_empty(),
+        );
+    }));
+    dialog.set_response_enabled("confirm", !name.trim().is_empty());
+
+    let response = dialog.choose_future().await;
+    let name = entry.text().trim().to_owned();
+    (response.as_str() == "confirm" && !name.is_empty()).then_some(name)
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, gtk::CompositeTemplate)]
+    #[template(resource = "/null/daknig/DewDuct/playlists_page.ui")]
+    pub struct DewPlaylistsPage {
+        // Template widgets
+        #[template_child]
+        playlists_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        empty_page: TemplateChild<adw::StatusPage>,
+        #[template_child]
+        lists_page: TemplateChild<gtk::ScrolledWindow>,
+        #[template_child]
+        lists: TemplateChild<gtk::ListBox>,
+
+        playlists: RefCell<Vec<LocalPlaylist>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DewPlaylistsPage {
+        const NAME: &'static str = "DewPlaylistsPage";
+        type Type = super::DewPlaylistsPage;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for DewPlaylistsPage {
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.update_rows();
+            glib::spawn_future_local(clone!(@weak self as page =>
+                 async move { page.load_state().await; }));
+        }
+    }
+    impl WidgetImpl for DewPlaylistsPage {}
+    impl BoxImpl for DewPlaylistsPage {}
+
+    #[gtk::template_callbacks]
+    impl DewPlaylistsPage {
+        fn window(&self) -> DewDuctWindow {
+            self.obj().root().and_downcast().unwrap()
+        }
+        /// Shows the change, and keeps it.
+        fn changed(&self) {
+            self.update_rows();
+            let path = util::data_path("playlists.json");
+            let playlists = Playlists {
+                playlists: self.playlists.borrow().clone(),
+            };
+            if let Err(err) = util::store_state(&path, &playlists) {
+                g_warning!("DewPlaylistsPage", "{:#}", err);
+            }
+        }
+        async fn load_state(&self) {
+            let path = util::data_path("playlists.json");
+            let loaded: Playlists = self
+                .window()
+                .spawn_blocking(move || util::load_state(&path))
+                .await
+                .unwrap_or_default();
+
+            // anything created while loading comes after.
+            let mut playlists = loaded.playlists;
+            playlists.extend(self.playlists.take());
+            self.playlists.replace(playlists);
+            self.update_rows();
+        }
+        fn update_rows(&self) {
+            while let Some(row) = self.lists.first_child() {
+                self.lists.remove(&row);
+            }
+            let playlists = self.playlists.borrow();
+            for playlist in playlists.iter() {
+                let row = adw::ActionRow::builder()
+                    .title(glib::markup_escape_text(&playlist.name))
+                    .subtitle(match playlist.videos.len() {
+                        1 => "1 video".to_owned(),
+                        n => format!("{n} videos"),
+                    })
+                    .activatable(true)
+                    .build();
+                row.add_suffix(&gtk::Image::from_icon_name(
+                    "go-next-symbolic",
+                ));
+                let id = playlist.id.clone();
+                row.connect_activated(
+                    clone!(@weak self as page => move |_| {
+                        page.window().show_local_playlist(&id);
+                    }),
+                );
+                self.lists.append(&row);
+            }
+            if playlists.is_empty() {
+                self.playlists_stack.set_visible_child(&*self.empty_page);
+            } else {
+                self.playlists_stack.set_visible_child(&*self.lists_page);
+            }
+        }
+
+        pub(crate) fn get(&self, id: &str) -> Option<LocalPlaylist> {
+            self.playlists
+                .borrow()
+                .iter()
+                .find(|playlist| playlist.id == id)
+                .cloned()
+        }
+        /// Gives back its id.
+        pub(crate) fn create(&self, name: String) -> String {
+            let id = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos()
+                .to_string();
+            self.playlists.borrow_mut().push(LocalPlaylist {
+                id: id.clone(),
+                name,
+                videos: vec![],
+            });
+            self.changed();
+            id
+        }
+        fn update(&self, id: &str, f: impl FnOnce(&mut LocalPlaylist)) {
+            let mut playlists = self.playlists.borrow_mut();
+            let Some(playlist) =
+                playlists.iter_mut().find(|playlist| playlist.id == id)
+            else {
+                return;
+            };
+            f(playlist);
+            drop(playlists);
+            self.changed();
+        }
+        pub(crate) fn rename(&self, id: &str, name: String) {
+            self.update(id, |playlist| playlist.name = name);
+        }
+        pub(crate) fn delete(&self, id: &str) {
+            self.playlists
+                .borrow_mut()
+                .retain(|playlist| playlist.id != id);
+            self.changed();
+        }
+        /// At the end, unless it is there already.
+        pub(crate) fn add_video(&self, id: &str, item: &DewYtItem) {
+            self.update(id, |playlist| {
+                let vid_id = item.id();
+                if !playlist.videos.iter().any(|vid| vid.id == vid_id) {
+                    playlist.videos.push(item.into());
+                }
+            });
+        }
+        pub(crate) fn remove_video(&self, id: &str, vid_id: &str) {
+            self.update(id, |playlist| {
+                playlist.videos.retain(|vid| vid.id != vid_id)
+            });
+        }
+        /// Lets the user pick a playlist, or create one, for the video.
+        pub(crate) async fn add_with_dialog(&self, item: &DewYtItem) {
+            enum Choice {
+                Existing(String),
+                New(String),
+            }
+            let window = self.window();
+            let dialog = adw::MessageDialog::new(
+                Some(&window),
+                Some("Add to Playlist"),
+                Some(&item.title()),
+            );
+            let chosen: Rc<RefCell<Option<Choice>>> = Rc::default();
+
+            let list = gtk::ListBox::builder()
+                .selection_mode(gtk::SelectionMode::None)
+                .css_classes(["boxed-list"])
+                .build();
+            for playlist in self.playlists.borrow().iter() {
+                let row = adw::ActionRow::builder()
+                    .title(glib::markup_escape_text(&playlist.name))
+                    .activatable(true)
+                    .build();
+                let id = playlist.id.clone();
+                row.connect_activated(
+                    clone!(@weak dialog, @strong chosen => move |_| {
+                        chosen.replace(Some(Choice::Existing(id.clone())));
+                        dialog.close();
+                    }),
+                );
+                list.append(&row);
+            }
+            let new_row = adw::EntryRow::builder()
+                .title("New Playlist")
+                .show_apply_button(true)
+                .build();
+            new_row.connect_apply(
+                clone!(@weak dialog, @strong chosen => move |entry| {
+                    let name = entry.text().trim().to_owned();
+                    if !name.is_empty() {
+                        chosen.replace(Some(Choice::New(name)));
+                        dialog.close();
+                    }
+                }),
+            );
+            list.append(&new_row);
+
+            dialog.set_extra_child(Some(&list));
+            dialog.add_responses(&[("cancel", "_Cancel")]);
+            dialog.set_close_response("cancel");
+            dialog.choose_future().await;
+
+            let id = match chosen.take() {
+                Some(Choice::Existing(id)) => id,
+                Some(Choice::New(name)) => self.create(name),
+                None => return,
+            };
+            self.add_video(&id, item);
+            if let Some(playlist) = self.get(&id) {
+                window.toast(&format!(
+                    "Added to “{}”",
+                    glib::markup_escape_text(&playlist.name)
+                ));
+            }
+        }
+        #[template_callback]
+        async fn new_playlist(&self) {
+            let window = self.window();
+            if let Some(name) =
+                ask_playlist_name(&window, "New Playlist", "", "_Create")
+                    .await
+            {
+                self.create(name);
+            }
+        }
+    }
+
+    #[derive(Default, Deserialize, Serialize)]
+    pub(super) struct Playlists {
+        playlists: Vec<LocalPlaylist>,
+    }
+}
+
+glib::wrapper! {
+    pub struct DewPlaylistsPage(ObjectSubclass<imp::DewPlaylistsPage>)
+        @extends gtk::Widget, gtk::Box,
+        @implements gio::ActionGroup, gio::ActionMap;
+}
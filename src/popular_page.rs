@@ -75,30 +75,28 @@ mod imp {
 
     #[gtk::template_callbacks]
     impl DewPopularPage {
-        fn async_invidious_client(&self) -> invidious::ClientAsync {
-            self.obj()
-                .root()
-                .and_downcast_ref::<crate::window::DewDuctWindow>()
-                .unwrap()
-                .async_invidious_client()
-        }
         fn window(&self) -> DewDuctWindow {
             self.obj().root().and_downcast().unwrap()
         }
         #[template_callback]
         async fn update_vids(&self) {
-            let invidious = self.async_invidious_client();
+            let pool = self.window().instance_pool();
 
             self.update_button.set_sensitive(false);
 
             let Ok(Some(popular)) = self
                 .window()
                 .spawn(async move {
-                    match invidious.popular(None).await {
+                    let popular = pool
+                        .request(|invidious| async move {
+                            invidious.popular(None).await
+                        })
+                        .await;
+                    match popular {
                         Err(err) => {
                             g_warning!(
                                 "DewPopularPage",
-                                "cant update page: {:#?}",
+                                "cant update page: {:#}",
                                 err
                             );
                             None
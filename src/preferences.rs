@@ -0,0 +1,313 @@
+/* preferences.rs
+ *
+ * Copyright 2023-2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::Cell;
+
+#[allow(unused_imports)]
+use adw::{prelude::*, subclass::prelude::*};
+use glib::{clone, g_warning};
+use gtk::glib;
+#[allow(unused_imports)]
+use gtk::{prelude::*, subclass::prelude::*};
+
+use crate::quality::HEIGHTS;
+use crate::settings::{
+    check_instance, normalize_instance, Downloader, PlaybackSource,
+    PlayerKind, KNOWN_INSTANCES,
+};
+use crate::window::DewDuctWindow;
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, gtk::CompositeTemplate)]
+    #[template(resource = "/null/daknig/DewDuct/preferences.ui")]
+    pub struct DewPreferencesWindow {
+        // Template widgets
+        #[template_child]
+        pub(super) instance_entry: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        known_instances: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        pub(super) default_quality_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub(super) playback_source_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub(super) player_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub(super) downloader_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub(super) captions_language_entry: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) download_captions_row: TemplateChild<adw::SwitchRow>,
+
+        /// While the quality choices are put in, which changes the
+        /// selection on its own.
+        filling_quality: Cell<bool>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DewPreferencesWindow {
+        const NAME: &'static str = "DewPreferencesWindow";
+        type Type = super::DewPreferencesWindow;
+        type ParentType = adw::PreferencesWindow;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for DewPreferencesWindow {}
+    impl WidgetImpl for DewPreferencesWindow {}
+    impl WindowImpl for DewPreferencesWindow {}
+    impl AdwWindowImpl for DewPreferencesWindow {}
+    impl PreferencesWindowImpl for DewPreferencesWindow {}
+
+    #[gtk::template_callbacks]
+    impl DewPreferencesWindow {
+        fn win(&self) -> DewDuctWindow {
+            self.obj().transient_for().and_downcast().unwrap()
+        }
+        /// Lists the ranked public instances, or the hardcoded ones if
+        /// the ranking is not there yet.
+        pub(super) fn fill_known_instances(&self) {
+            let ranked = self.win().ranked_instances();
+            let rows: Vec<(String, String)> = if ranked.is_empty() {
+                KNOWN_INSTANCES
+                    .iter()
+                    .map(|url| (url.to_string(), String::new()))
+                    .collect()
+            } else {
+                ranked
+                    .into_iter()
+                    .map(|instance| {
+                        let subtitle = format!(
+                            "{} ms, version {}",
+                            instance.latency_ms, instance.version
+                        );
+                        (instance.url, subtitle)
+                    })
+                    .collect()
+            };
+
+            for (url, subtitle) in rows {
+                let row = adw::ActionRow::builder()
+                    .title(url)
+                    .subtitle(subtitle)
+                    .activatable(true)
+                    .build();
+                row.connect_activated(clone!(@weak self as prefs =>
+                    move |row| {
+                        let instance: String = row.title().into();
+                        prefs.instance_entry.set_text(&instance);
+                        glib::spawn_future_local(async move {
+                            prefs.set_instance(instance).await
+                        });
+                    }
+                ));
+                self.known_instances.add(&row);
+            }
+        }
+        /// The first choice is the best quality, then every height.
+        pub(super) fn fill_default_quality(&self, default: Option<u32>) {
+            let choices: Vec<String> = std::iter::once("Best".to_owned())
+                .chain(HEIGHTS.iter().map(|height| format!("{height}p")))
+                .collect();
+            let choices: Vec<&str> =
+                choices.iter().map(String::as_str).collect();
+            let selected = default
+                .and_then(|default| {
+                    HEIGHTS.iter().position(|height| *height == default)
+                })
+                .map_or(0, |i| i + 1);
+
+            let row = &self.default_quality_row;
+            self.filling_quality.set(true);
+            row.set_model(Some(&gtk::StringList::new(&choices)));
+            row.set_selected(selected as u32);
+            self.filling_quality.set(false);
+        }
+        #[template_callback]
+        fn default_quality_selected(&self) {
+            // not a choice of the user.
+            if self.filling_quality.get() {
+                return;
+            }
+            let Some(win) = self.obj().transient_for() else {
+                return;
+            };
+            let win: DewDuctWindow = win.downcast().unwrap();
+            let selected = self.default_quality_row.selected() as usize;
+            let default_quality = match selected {
+                0 => None,
+                i => HEIGHTS.get(i - 1).copied(),
+            };
+            win.update_settings(|settings| {
+                settings.default_quality = default_quality
+            });
+        }
+        #[template_callback]
+        fn playback_source_selected(&self) {
+            let Some(win) = self.obj().transient_for() else {
+                return;
+            };
+            let win: DewDuctWindow = win.downcast().unwrap();
+            let playback_source = match self.playback_source_row.selected()
+            {
+                1 => PlaybackSource::Direct,
+                2 => PlaybackSource::Proxied,
+                _ => PlaybackSource::Ytdl,
+            };
+            win.update_settings(|settings| {
+                settings.playback_source = playback_source
+            });
+        }
+        #[template_callback]
+        fn player_selected(&self) {
+            let Some(win) = self.obj().transient_for() else {
+                return;
+            };
+            let win: DewDuctWindow = win.downcast().unwrap();
+            let player = match self.player_row.selected() {
+                1 => PlayerKind::Embedded,
+                _ => PlayerKind::Mpv,
+            };
+            win.update_settings(|settings| settings.player = player);
+        }
+        #[template_callback]
+        fn downloader_selected(&self) {
+            let Some(win) = self.obj().transient_for() else {
+                return;
+            };
+            let win: DewDuctWindow = win.downcast().unwrap();
+            let downloader = match self.downloader_row.selected() {
+                1 => Downloader::Builtin,
+                _ => Downloader::Ytdl,
+            };
+            win.update_settings(|settings| {
+                settings.downloader = downloader
+            });
+        }
+        #[template_callback]
+        fn captions_language_apply(&self, entry: &adw::EntryRow) {
+            let language = entry.text().trim().to_owned();
+            entry.set_text(&language);
+            self.win().update_settings(|settings| {
+                settings.captions_language =
+                    Some(language).filter(|language| !language.is_empty())
+            });
+        }
+        #[template_callback]
+        fn download_captions_toggled(&self) {
+            let Some(win) = self.obj().transient_for() else {
+                return;
+            };
+            let win: DewDuctWindow = win.downcast().unwrap();
+            let download_captions = self.download_captions_row.is_active();
+            win.update_settings(|settings| {
+                settings.download_captions = download_captions
+            });
+        }
+        #[template_callback]
+        async fn instance_apply(&self, entry: &adw::EntryRow) {
+            let instance = normalize_instance(&entry.text());
+            entry.set_text(&instance);
+            self.set_instance(instance).await;
+        }
+        async fn set_instance(&self, instance: String) {
+            let win = self.win();
+
+            self.instance_entry.set_sensitive(false);
+            let status = win.spawn(check_instance(instance.clone())).await;
+            self.instance_entry.set_sensitive(true);
+
+            let toast = match status {
+                Ok(Ok(())) => {
+                    self.instance_entry.remove_css_class("error");
+                    win.set_instance(instance.clone());
+                    format!("Now using {instance}")
+                }
+                Ok(Err(err)) => {
+                    g_warning!("DewPreferences", "{:#}", err);
+                    self.instance_entry.add_css_class("error");
+                    err.to_string()
+                }
+                Err(err) => {
+                    g_warning!(
+                        "DewPreferences",
+                        "this should not crash: {err}"
+                    );
+                    return;
+                }
+            };
+            self.obj().add_toast(adw::Toast::new(&toast));
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct DewPreferencesWindow(ObjectSubclass<imp::DewPreferencesWindow>)
+        @extends gtk::Widget, gtk::Window, adw::Window, adw::PreferencesWindow,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget,
+            gtk::Native, gtk::Root, gtk::ShortcutManager;
+}
+
+impl DewPreferencesWindow {
+    pub fn new(win: &DewDuctWindow) -> Self {
+        let obj: Self = glib::Object::builder()
+            .property("transient-for", win)
+            .build();
+        obj.imp().instance_entry.set_text(&win.settings().instance);
+        obj.imp()
+            .fill_default_quality(win.settings().default_quality);
+        obj.imp().playback_source_row.set_selected(
+            match win.settings().playback_source {
+                PlaybackSource::Ytdl => 0,
+                PlaybackSource::Direct => 1,
+                PlaybackSource::Proxied => 2,
+            },
+        );
+        obj.imp()
+            .player_row
+            .set_selected(match win.settings().player {
+                PlayerKind::Mpv => 0,
+                PlayerKind::Embedded => 1,
+            });
+        obj.imp().downloader_row.set_selected(
+            match win.settings().downloader {
+                Downloader::Ytdl => 0,
+                Downloader::Builtin => 1,
+            },
+        );
+        obj.imp().captions_language_entry.set_text(
+            win.settings().captions_language.as_deref().unwrap_or(""),
+        );
+        obj.imp()
+            .download_captions_row
+            .set_active(win.settings().download_captions);
+        obj.imp().fill_known_instances();
+        obj
+    }
+}
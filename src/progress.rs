@@ -0,0 +1,175 @@
+/* progress.rs
+ *
+ * Copyright 2023-2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use glib::{g_warning, user_data_dir};
+use gtk::glib;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::util::progress;
+
+/// The progress is written to disk at most that often while playing.
+const STORE_INTERVAL: Duration = Duration::from_secs(10);
+/// Past that fraction, a video counts as watched and is not resumed.
+const NEARLY_FINISHED: f64 = 0.95;
+/// Not worth resuming before that, in seconds.
+const BARELY_STARTED: f64 = 5.;
+
+/// How far the user got in a video, in seconds.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub struct WatchProgress {
+    pub position: f64,
+    pub length: f64,
+}
+
+impl WatchProgress {
+    /// Between 0 and 1; 0 if the length is not known.
+    pub fn fraction(&self) -> f64 {
+        if self.length > 0. {
+            (self.position / self.length).clamp(0., 1.)
+        } else {
+            0.
+        }
+    }
+
+    /// Where playback should continue from, if anywhere.
+    pub fn resume_position(&self) -> Option<f64> {
+        let resumable = self.position > BARELY_STARTED
+            && self.fraction() < NEARLY_FINISHED;
+        resumable.then_some(self.position)
+    }
+}
+
+/// The progress in every video ever played, by video id. Shared by the
+/// whole app, see `util::progress`.
+#[derive(Debug)]
+pub struct DewProgress {
+    path: PathBuf,
+    videos: Mutex<HashMap<String, WatchProgress>>,
+}
+
+impl Default for DewProgress {
+    fn default() -> Self {
+        let path = user_data_dir().join("DewDuct/").join("progress.json");
+        let videos = match fs::read(&path) {
+            Ok(contents) => serde_json::from_slice(&contents)
+                .unwrap_or_else(|err| {
+                    g_warning!(
+                        "DewProgress",
+                        "malformed progress file {}: {}",
+                        path.display(),
+                        err
+                    );
+                    HashMap::new()
+                }),
+            Err(_) => HashMap::new(),
+        };
+        Self {
+            path,
+            videos: Mutex::new(videos),
+        }
+    }
+}
+
+impl DewProgress {
+    pub fn get(&self, id: &str) -> Option<WatchProgress> {
+        self.videos.lock().unwrap().get(id).copied()
+    }
+
+    /// What the thumbnails show: 0 for videos never played.
+    pub fn fraction(&self, id: &str) -> f64 {
+        self.get(id).map_or(0., |progress| progress.fraction())
+    }
+
+    pub fn set(&self, id: &str, progress: WatchProgress) {
+        self.videos.lock().unwrap().insert(id.to_owned(), progress);
+    }
+
+    pub fn store(&self) -> anyhow::Result<()> {
+        let contents = serde_json::to_vec(&*self.videos.lock().unwrap())?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = File::create(&self.path).with_context(|| {
+            format!("unable to create file {}", self.path.display())
+        })?;
+        std::io::Write::write_all(&mut file, &contents).with_context(|| {
+            format!("unable to write progress to {}", self.path.display())
+        })
+    }
+}
+
+/// Follows what a player reports about one video, and writes it to disk
+/// every now and then, and once done.
+#[derive(Debug)]
+pub struct ProgressTracker {
+    id: String,
+    watched: WatchProgress,
+    last_store: Instant,
+}
+
+impl ProgressTracker {
+    pub fn new(id: String) -> Self {
+        Self {
+            watched: progress().get(&id).unwrap_or_default(),
+            id,
+            last_store: Instant::now(),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Takes `time-pos` and `duration`, as mpv calls them, in seconds.
+    /// Returns whether it was one of those.
+    pub fn update(&mut self, property: &str, value: f64) -> bool {
+        match property {
+            "time-pos" => self.watched.position = value,
+            "duration" => self.watched.length = value,
+            _ => return false,
+        }
+        progress().set(&self.id, self.watched);
+        if self.last_store.elapsed() > STORE_INTERVAL {
+            self.last_store = Instant::now();
+            store_progress();
+        }
+        true
+    }
+}
+
+impl Drop for ProgressTracker {
+    fn drop(&mut self) {
+        store_progress();
+    }
+}
+
+pub fn store_progress() {
+    if let Err(err) = progress().store() {
+        g_warning!("DewProgress", "can't store progress: {:#}", err);
+    }
+}
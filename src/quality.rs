@@ -0,0 +1,328 @@
+/* quality.rs
+ *
+ * Copyright 2023-2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use invidious::hidden::FormatStream;
+use invidious::video::Video;
+
+/// The resolutions offered as the default quality in the preferences.
+pub const HEIGHTS: &[u32] = &[2160, 1440, 1080, 720, 480, 360, 240, 144];
+
+/// One of the ways a video can be played.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Quality {
+    /// What the user sees, like `720p60`.
+    pub label: String,
+    pub height: u32,
+    /// For `--ytdl-format`; itags are the format ids of yt-dlp.
+    pub ytdl_format: String,
+    /// The streams themselves: the video, and the audio if it does not
+    /// come with it.
+    pub urls: Vec<String>,
+}
+
+/// `1080p60` is 1080 pixels high.
+fn parse_height(label: &str) -> Option<u32> {
+    let digits: String =
+        label.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// Proxied streams (from `local=true`) come without the instance.
+pub fn resolve_url(url: &str, instance: &str) -> String {
+    if url.starts_with('/') {
+        format!("{instance}{url}")
+    } else {
+        url.to_owned()
+    }
+}
+
+/// The audio stream with the highest bitrate.
+pub fn best_audio_url(vid: &Video, instance: &str) -> Option<String> {
+    vid.adaptive_formats
+        .iter()
+        .filter(|format| format.r#type.starts_with("audio/"))
+        .max_by_key(|format| format.bitrate.parse::<u64>().unwrap_or(0))
+        .map(|format| resolve_url(&format.url, instance))
+}
+
+/// Every resolution the video comes in, highest first. Streams with both
+/// audio and video are preferred; video-only streams get the best audio
+/// added to them. `instance` is the one that answered with `vid`.
+pub fn qualities(vid: &Video, instance: &str) -> Vec<Quality> {
+    let best_audio = best_audio_url(vid, instance);
+
+    let muxed = vid.format_streams.iter().filter_map(|stream| {
+        Some(Quality {
+            label: stream.quality_label.clone(),
+            height: parse_height(&stream.quality_label)?,
+            ytdl_format: stream.itag.clone(),
+            urls: vec![resolve_url(&stream.url, instance)],
+        })
+    });
+    let video_only = vid
+        .adaptive_formats
+        .iter()
+        .filter(|format| format.r#type.starts_with("video/"))
+        .filter_map(|format| {
+            // the client reads `qualityLabel` into `quality`, not
+            // `quality_label`.
+            let label = match format.quality_label.is_empty() {
+                true => &format.quality,
+                false => &format.quality_label,
+            };
+            Some(Quality {
+                label: label.clone(),
+                height: parse_height(label)?,
+                ytdl_format: format!("{}+bestaudio", format.itag),
+                urls: std::iter::once(resolve_url(&format.url, instance))
+                    .chain(best_audio.clone())
+                    .collect(),
+            })
+        });
+
+    let mut ret: Vec<Quality> = vec![];
+    for quality in muxed.chain(video_only) {
+        if !ret.iter().any(|known| known.label == quality.label) {
+            ret.push(quality);
+        }
+    }
+    // stable, so muxed streams stay first among the same height.
+    ret.sort_by_key(|quality| std::cmp::Reverse(quality.height));
+    ret
+}
+
+/// For saving as is: the stream with both audio and video that is the
+/// highest not above `max_height`, or the lowest there is, with its
+/// container, like `mp4`.
+pub fn muxed_stream(
+    vid: &Video,
+    instance: &str,
+    max_height: Option<u32>,
+) -> Option<(String, String)> {
+    let mut streams: Vec<(u32, &FormatStream)> = vid
+        .format_streams
+        .iter()
+        .filter_map(|stream| {
+            Some((parse_height(&stream.quality_label)?, stream))
+        })
+        .collect();
+    streams.sort_by_key(|(height, _)| std::cmp::Reverse(*height));
+    let max_height = max_height.unwrap_or(u32::MAX);
+    let (_, stream) = streams
+        .iter()
+        .find(|(height, _)| *height <= max_height)
+        .or(streams.last())?;
+    Some((resolve_url(&stream.url, instance), stream.container.clone()))
+}
+
+/// The highest quality not above `max_height`, or the lowest there is.
+/// `None` as the max means the best.
+pub fn preferred(qualities: &[Quality], max_height: Option<u32>) -> usize {
+    let max_height = max_height.unwrap_or(u32::MAX);
+    qualities
+        .iter()
+        .position(|quality| quality.height <= max_height)
+        .unwrap_or(qualities.len().saturating_sub(1))
+}
+
+/// For when the formats are not known, like for live streams.
+pub fn fallback_ytdl_format(max_height: Option<u32>) -> String {
+    match max_height {
+        Some(height) => format!("best[height<={height}]/best"),
+        None => "best".into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    const INSTANCE: &str = "https://inv.example";
+
+    fn muxed(label: &str, url: &str) -> Value {
+        json!({
+            "url": url, "itag": label, "type": "video/mp4",
+            "quality": "medium", "container": "mp4", "encoding": "h264",
+            "qualityLabel": label, "resolution": label, "size": "",
+        })
+    }
+
+    fn adaptive(
+        r#type: &str,
+        label: &str,
+        bitrate: u64,
+        url: &str,
+    ) -> Value {
+        json!({
+            "url": url, "itag": format!("{label}{bitrate}"),
+            "type": r#type, "bitrate": bitrate.to_string(),
+            "qualityLabel": label,
+        })
+    }
+
+    fn video(
+        format_streams: Vec<Value>,
+        adaptive_formats: Vec<Value>,
+    ) -> Video {
+        serde_json::from_value(json!({
+            "title": "", "videoId": "id", "videoThumbnails": [],
+            "storyboards": [], "description": "", "descriptionHtml": "",
+            "published": 0, "publishedText": "", "keywords": [],
+            "viewCount": 0, "likeCount": 0, "dislikeCount": 0,
+            "paid": false, "premium": false, "isFamilyFriendly": true,
+            "allowedRegions": [], "genre": "", "genreUrl": "",
+            "author": "", "authorId": "", "authorUrl": "",
+            "authorThumbnails": [], "subCountText": "", "lengthSeconds": 0,
+            "allowRatings": true, "rating": 0.0, "isListed": true,
+            "liveNow": false, "isUpcoming": false, "dashUrl": "",
+            "adaptiveFormats": adaptive_formats,
+            "formatStreams": format_streams,
+            "captions": [], "recommendedVideos": [],
+        }))
+        .unwrap()
+    }
+
+    fn sample() -> Video {
+        video(
+            vec![
+                muxed("360p", "/latest_version?itag=18"),
+                muxed("720p", "https://cdn.example/22"),
+            ],
+            vec![
+                adaptive(
+                    "video/mp4",
+                    "1080p60",
+                    0,
+                    "https://cdn.example/299",
+                ),
+                adaptive("video/mp4", "720p", 0, "https://cdn.example/136"),
+                adaptive(
+                    "audio/mp4",
+                    "",
+                    128000,
+                    "https://cdn.example/140",
+                ),
+                adaptive(
+                    "audio/webm",
+                    "",
+                    160000,
+                    "/videoplayback?itag=251",
+                ),
+            ],
+        )
+    }
+
+    fn quality(height: u32) -> Quality {
+        Quality {
+            label: format!("{height}p"),
+            height,
+            ytdl_format: String::new(),
+            urls: vec![],
+        }
+    }
+
+    #[test]
+    fn relative_urls() {
+        assert_eq!(
+            resolve_url("/latest_version?itag=18", INSTANCE),
+            "https://inv.example/latest_version?itag=18"
+        );
+        assert_eq!(
+            resolve_url("https://cdn.example/22", INSTANCE),
+            "https://cdn.example/22"
+        );
+    }
+
+    #[test]
+    fn heights() {
+        assert_eq!(parse_height("1080p60"), Some(1080));
+        assert_eq!(parse_height("144p"), Some(144));
+        assert_eq!(parse_height("audio"), None);
+    }
+
+    #[test]
+    fn every_height_highest_first() {
+        let qualities = qualities(&sample(), INSTANCE);
+        let labels: Vec<&str> = qualities
+            .iter()
+            .map(|quality| quality.label.as_str())
+            .collect();
+        assert_eq!(labels, ["1080p60", "720p", "360p"]);
+
+        // video only, with the best audio.
+        assert_eq!(qualities[0].ytdl_format, "1080p600+bestaudio");
+        assert_eq!(
+            qualities[0].urls,
+            [
+                "https://cdn.example/299",
+                "https://inv.example/videoplayback?itag=251",
+            ]
+        );
+        // the muxed one wins over the video only one.
+        assert_eq!(qualities[1].urls, ["https://cdn.example/22"]);
+        assert_eq!(
+            qualities[2].urls,
+            ["https://inv.example/latest_version?itag=18"]
+        );
+    }
+
+    #[test]
+    fn preferred_height() {
+        let qualities = [quality(1080), quality(720), quality(360)];
+        assert_eq!(preferred(&qualities, None), 0);
+        assert_eq!(preferred(&qualities, Some(720)), 1);
+        assert_eq!(preferred(&qualities, Some(480)), 2);
+        // nothing low enough, the lowest then.
+        assert_eq!(preferred(&qualities, Some(144)), 2);
+        assert_eq!(preferred(&[], Some(720)), 0);
+    }
+
+    #[test]
+    fn muxed_by_height() {
+        let vid = sample();
+        let url = |max_height| {
+            muxed_stream(&vid, INSTANCE, max_height).map(|(url, _)| url)
+        };
+        assert_eq!(url(None).as_deref(), Some("https://cdn.example/22"));
+        assert_eq!(
+            url(Some(480)).as_deref(),
+            Some("https://inv.example/latest_version?itag=18")
+        );
+        assert_eq!(
+            url(Some(144)).as_deref(),
+            Some("https://inv.example/latest_version?itag=18")
+        );
+        assert_eq!(muxed_stream(&vid, INSTANCE, None).unwrap().1, "mp4");
+        assert_eq!(
+            muxed_stream(&video(vec![], vec![]), INSTANCE, None),
+            None
+        );
+    }
+
+    #[test]
+    fn fallback_format() {
+        assert_eq!(fallback_ytdl_format(None), "best");
+        assert_eq!(
+            fallback_ytdl_format(Some(720)),
+            "best[height<=720]/best"
+        );
+    }
+}
@@ -0,0 +1,175 @@
+/* queue_page.rs
+ *
+ * Copyright 2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+#[allow(unused_imports)]
+use adw::{prelude::*, subclass::prelude::*};
+use glib::{clone, g_warning, Variant};
+use gtk::{gio, glib};
+#[allow(unused_imports)]
+use gtk::{prelude::*, subclass::prelude::*};
+
+use crate::player_bar::BarMode;
+use crate::window::DewDuctWindow;
+use crate::yt_item_list::*;
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, gtk::CompositeTemplate)]
+    #[template(resource = "/null/daknig/DewDuct/queue_page.ui")]
+    pub struct DewQueuePage {
+        // Template widgets
+        #[template_child]
+        play_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        clear_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        queue_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        empty_page: TemplateChild<adw::StatusPage>,
+        #[template_child]
+        vid_list: TemplateChild<DewYtItemList>,
+        #[template_child]
+        row_menu: TemplateChild<gio::MenuModel>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DewQueuePage {
+        const NAME: &'static str = "DewQueuePage";
+        type Type = super::DewQueuePage;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+            klass.install_action(
+                "queue.move-up",
+                Some(glib::VariantTy::STRING),
+                |page, _, param| page.imp().move_by(param, -1),
+            );
+            klass.install_action(
+                "queue.move-down",
+                Some(glib::VariantTy::STRING),
+                |page, _, param| page.imp().move_by(param, 1),
+            );
+            klass.install_action(
+                "queue.remove",
+                Some(glib::VariantTy::STRING),
+                Self::Type::remove,
+            );
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for DewQueuePage {
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.vid_list.set_is_queue(true);
+            self.vid_list.set_row_menu(&*self.row_menu);
+            self.vid_list.connect_items_changed(
+                clone!(@weak self as page => move |list| {
+                    let empty = list.n_items() == 0;
+                    page.play_button.set_sensitive(!empty);
+                    page.clear_button.set_sensitive(!empty);
+                    if empty {
+                        page.queue_stack
+                            .set_visible_child(&*page.empty_page);
+                    } else {
+                        page.queue_stack
+                            .set_visible_child(&*page.vid_list);
+                    }
+                    // the player might have nothing to skip to anymore.
+                    if let Some(window) =
+                        page.obj().root().and_downcast::<DewDuctWindow>()
+                    {
+                        window.queue_changed();
+                    }
+                }),
+            );
+        }
+    }
+    impl WidgetImpl for DewQueuePage {}
+    impl BoxImpl for DewQueuePage {}
+
+    #[gtk::template_callbacks]
+    impl DewQueuePage {
+        fn window(&self) -> DewDuctWindow {
+            self.obj().root().and_downcast().unwrap()
+        }
+        /// At the end, unless they are there already.
+        pub(crate) fn add(&self, items: &[DewYtItem]) {
+            let mut queued: Vec<DewYtItem> =
+                self.vid_list.get_vec().into_iter().collect();
+            for item in items {
+                if !queued.iter().any(|old| old.id() == item.id()) {
+                    queued.push(item.clone());
+                }
+            }
+            self.vid_list.set_from_vec(queued);
+        }
+        /// Takes out the next video to play.
+        pub(crate) fn pop_front(&self) -> Option<DewYtItem> {
+            let first = self.vid_list.get_vec().into_iter().next()?;
+            self.vid_list.del_item_with_id(first.id());
+            Some(first)
+        }
+        pub(crate) fn is_empty(&self) -> bool {
+            self.vid_list.get_vec().into_iter().next().is_none()
+        }
+        fn move_by(&self, param: Option<&Variant>, by: i32) {
+            let Some(id) = param.and_then(|param| param.get::<String>())
+            else {
+                g_warning!("DewQueuePage", "moving needs an id");
+                return;
+            };
+            self.vid_list.move_item_with_id(&id, by);
+        }
+        pub(super) fn remove(&self, id: String) {
+            self.vid_list.del_item_with_id(id);
+        }
+        #[template_callback]
+        async fn play_clicked(&self) {
+            self.window().play_queue(BarMode::Video).await;
+        }
+        #[template_callback]
+        fn clear_clicked(&self) {
+            self.vid_list.set_from_vec(vec![]);
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct DewQueuePage(ObjectSubclass<imp::DewQueuePage>)
+        @extends gtk::Widget, gtk::Box,
+        @implements gio::ActionGroup, gio::ActionMap;
+}
+
+impl DewQueuePage {
+    pub fn remove(&self, _: &str, param: Option<&Variant>) {
+        let Some(id) = param.and_then(|param| param.get::<String>()) else {
+            g_warning!("DewQueuePage", "queue.remove needs an id");
+            return;
+        };
+        self.imp().remove(id);
+    }
+}
@@ -27,7 +27,6 @@ use gtk::{gio, glib};
 use gtk::{prelude::*, subclass::prelude::*};
 
 use html_escape::decode_html_entities;
-use invidious::hidden::SearchItem;
 use invidious::ClientAsyncTrait;
 use urlencoding::encode;
 
@@ -100,19 +99,22 @@ mod imp {
 
             // qeury for search results
             let query_transformed = format!("q={}", encode(&query));
-            let client = self.obj().async_invidious_client();
+            let pool = self.window().instance_pool();
             let search_results = self
                 .window()
                 .spawn(async move {
-                    match client.search(Some(&query_transformed)).await {
+                    let search = pool
+                            .request(|invidious| {
+                                let query = query_transformed.clone();
+                                async move {
+                                    invidious.search(Some(&query)).await
+                                }
+                            })
+                            .await;
+                    match search {
                         Ok(search) => search.items,
                         Err(err) => {
-                            g_warning!(
-                                "Dew",
-                                "instance {}; no results: {:?}",
-                                &client.instance,
-                                err
-                            );
+                            g_warning!("Dew", "no results: {:#}", err);
                             vec![]
                         }
                     }
@@ -130,15 +132,8 @@ mod imp {
 
             {
                 // actually putting in the items
-                let search_results: Vec<_> = search_results
-                    .into_iter()
-                    .filter(|x| {
-                        // we only support these types for now...
-                        matches!(x, SearchItem::Channel { .. })
-                            || matches!(x, SearchItem::Video { .. })
-                    })
-                    .map(|x| x.into())
-                    .collect();
+                let search_results: Vec<_> =
+                    search_results.into_iter().map(|x| x.into()).collect();
 
                 self.results_page.set_from_vec(search_results);
             }
@@ -146,7 +141,7 @@ mod imp {
         #[template_callback]
         pub(crate) async fn search_changed(&self, entry: &SearchEntry) {
             // glib::g_warning!("Dew", "search_changed");
-            let client = self.obj().async_invidious_client();
+            let pool = self.window().instance_pool();
 
             // get the dang results, errors = no results
             let query = entry.text().to_string().to_owned();
@@ -157,10 +152,15 @@ mod imp {
             // encode to make utf8 work
             let query_transformed = format!("q={}", encode(&query));
             let search_suggestions = self.window().spawn(async move {
-                match client
-                    .search_suggestions(Some(&query_transformed))
-                    .await
-                {
+                let search = pool
+                    .request(|invidious| {
+                        let query = query_transformed.clone();
+                        async move {
+                            invidious.search_suggestions(Some(&query)).await
+                        }
+                    })
+                    .await;
+                match search {
                     Ok(search) => {
                         g_warning!(
                             "DewSearch",
@@ -171,7 +171,7 @@ mod imp {
                         search.suggestions
                     }
                     Err(err) => {
-                        g_warning!("DewSearch", "no results: {:?}", err);
+                        g_warning!("DewSearch", "no results: {:#}", err);
                         vec![]
                     }
                 }
@@ -218,9 +218,4 @@ impl DewSearchPage {
     pub fn search_entry(&self) -> &SearchEntry {
         &self.imp().search_entry
     }
-    pub fn async_invidious_client(&self) -> invidious::ClientAsync {
-        let window: crate::window::DewDuctWindow =
-            self.root().and_downcast().unwrap();
-        window.async_invidious_client()
-    }
 }
@@ -0,0 +1,173 @@
+/* settings.rs
+ *
+ * Copyright 2023-2024 DaKnig
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+use glib::{g_warning, user_config_dir};
+use gtk::glib;
+
+use anyhow::Context;
+use invidious::{ClientAsync, ClientAsyncTrait, MethodAsync};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_INSTANCE: &str = "https://invidious.fdn.fr";
+
+/// Offered in the preferences, so the user does not have to type them.
+pub const KNOWN_INSTANCES: &[&str] = &[
+    "https://invidious.fdn.fr",
+    "https://yewtu.be",
+    "https://inv.nadeko.net",
+    "https://invidious.nerdvpn.de",
+    "https://invidious.privacyredirect.com",
+];
+
+/// Where the player gets the video from.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaybackSource {
+    /// mpv finds the streams by itself, with yt-dlp.
+    #[default]
+    Ytdl,
+    /// The stream URLs from the Invidious response, straight from YouTube.
+    Direct,
+    /// Same, but the instance proxies the streams.
+    Proxied,
+}
+
+/// What plays the videos.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayerKind {
+    /// mpv, in its own window.
+    #[default]
+    Mpv,
+    /// Inside the video page; needs streams with both audio and video.
+    Embedded,
+}
+
+/// What downloads the videos.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Downloader {
+    #[default]
+    Ytdl,
+    /// Our own, straight from the Invidious stream URLs; only streams
+    /// with both audio and video, as nothing merges them.
+    Builtin,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DewSettings {
+    /// The Invidious instance used for all the requests.
+    pub instance: String,
+    /// The highest resolution picked by default; `None` for the best.
+    pub default_quality: Option<u32>,
+    pub playback_source: PlaybackSource,
+    pub player: PlayerKind,
+    pub downloader: Downloader,
+    /// The language code of the captions picked by default, like `en`;
+    /// `None` for no captions.
+    pub captions_language: Option<String>,
+    /// Captions in that language are saved next to downloaded videos.
+    pub download_captions: bool,
+}
+
+impl Default for DewSettings {
+    fn default() -> Self {
+        Self {
+            instance: DEFAULT_INSTANCE.into(),
+            default_quality: Some(480),
+            playback_source: PlaybackSource::default(),
+            player: PlayerKind::default(),
+            downloader: Downloader::default(),
+            captions_language: None,
+            download_captions: false,
+        }
+    }
+}
+
+impl DewSettings {
+    fn file_path() -> PathBuf {
+        lazy_static! {
+            static ref SETTINGS: PathBuf =
+                user_config_dir().join("DewDuct/").join("settings.json");
+        }
+        SETTINGS.to_path_buf()
+    }
+
+    /// Missing or broken settings files give the defaults.
+    pub fn load() -> Self {
+        let path = Self::file_path();
+        let Ok(contents) = fs::read(&path) else {
+            return Self::default();
+        };
+        serde_json::from_slice(&contents).unwrap_or_else(|err| {
+            g_warning!(
+                "DewSettings",
+                "malformed settings file {}: {}",
+                path.display(),
+                err
+            );
+            Self::default()
+        })
+    }
+
+    pub fn store(&self) -> anyhow::Result<()> {
+        let path = Self::file_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = File::create(&path).with_context(|| {
+            format!("unable to create settings file {}", path.display())
+        })?;
+        serde_json::to_writer_pretty(file, self).with_context(|| {
+            format!("unable to write settings to {}", path.display())
+        })
+    }
+}
+
+/// Turns whatever the user typed into something usable as an instance:
+/// `yewtu.be/` becomes `https://yewtu.be`.
+pub fn normalize_instance(url: &str) -> String {
+    let url = url.trim().trim_end_matches('/');
+    if url.starts_with("https://") || url.starts_with("http://") {
+        url.to_owned()
+    } else {
+        format!("https://{url}")
+    }
+}
+
+/// Does a test API call, to know if the instance is usable at all.
+pub async fn check_instance(instance: String) -> anyhow::Result<()> {
+    let client = ClientAsync::new(instance.clone(), MethodAsync::default());
+    match client.stats(None).await {
+        Ok(_) => Ok(()),
+        Err(err) => anyhow::bail!("{instance} is not usable: {err}"),
+    }
+}
@@ -82,13 +82,6 @@ mod imp {
         ) -> glib::signal::SignalHandlerId {
             self.subs_list.connect_items_changed(f)
         }
-        fn async_invidious_client(&self) -> invidious::ClientAsync {
-            self.obj()
-                .root()
-                .and_downcast_ref::<crate::window::DewDuctWindow>()
-                .unwrap()
-                .async_invidious_client()
-        }
         fn window(&self) -> DewDuctWindow {
             self.obj().root().and_downcast().unwrap()
         }
@@ -126,6 +119,9 @@ mod imp {
                 })
                 .unwrap();
         }
+        pub fn subscriptions(&self) -> Vec<DewYtItem> {
+            self.subs_list.get_vec().into_iter().collect()
+        }
         pub fn del_subscription(&self, id: String) {
             self.subs_list.del_item_with_id(id);
             self.store_state();
@@ -142,8 +138,14 @@ mod imp {
                 return Ok(());
             }
 
-            let item =
-                self.async_invidious_client().channel(&id, None).await;
+            let item = self
+                .window()
+                .instance_pool()
+                .request(|invidious| {
+                    let id = id.clone();
+                    async move { invidious.channel(&id, None).await }
+                })
+                .await;
 
             match item {
                 Ok(item) => {
@@ -155,7 +157,7 @@ mod imp {
                 }
                 Err(e) => {
                     let e_str =
-                        format!("Unable to subscribe to {}: {}", &id, e);
+                        format!("Unable to subscribe to {}: {:#}", &id, e);
                     g_warning!("DewSubscriptionsPage", "{}", e_str);
                     anyhow::bail!(e_str);
                 }
@@ -253,12 +255,17 @@ mod imp {
                     );
                     vec![]
                 });
-            let invidious =
-                std::sync::Arc::new(self.async_invidious_client());
+            let pool = self.window().instance_pool();
             let channels_or_errors: Vec<_> = futures::stream::iter(subs)
                 .map(|id| {
-                    let invidious = invidious.clone();
-                    async move { invidious.channel(&id, None).await }
+                    let pool = pool.clone();
+                    async move {
+                        pool.request(|invidious| {
+                            let id = id.clone();
+                            async move { invidious.channel(&id, None).await }
+                        })
+                        .await
+                    }
                 })
                 .buffer_unordered(10)
                 .collect()
@@ -91,7 +91,7 @@ impl DewThumbnail {
             .set_text(&format!("{}{:02}:{:02}", hrs_str, mins, secs));
     }
 
-    fn set_progress(&self, watched_progress: f64) {
+    pub(crate) fn set_progress(&self, watched_progress: f64) {
         self.imp()
             .watched_progress
             .get()
@@ -1,14 +1,36 @@
 use crate::cache::DewCache;
+use crate::progress::DewProgress;
+use anyhow::Context;
+use gtk::glib::{g_warning, user_data_dir};
 use humantime::format_duration;
 use once_cell::sync::OnceCell;
+use serde::{de::DeserializeOwned, Serialize};
+use std::fs::{read, File};
 use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::time::{Duration, SystemTime, SystemTimeError, UNIX_EPOCH};
 
 pub(crate) fn format_rel_time(duration: Duration) -> String {
     let s: String = format_duration(duration).to_string();
     s.split_whitespace().next().unwrap().to_owned()
 }
 
+/// Like "3 days ago", for a unix timestamp; or "in 2 hours" for upcoming
+/// things.
+pub(crate) fn format_published(
+    published: u64,
+) -> Result<String, SystemTimeError> {
+    let now = SystemTime::now();
+    let published: SystemTime = UNIX_EPOCH + Duration::from_secs(published);
+    if now > published {
+        now.duration_since(published)
+            .map(|duration| format_rel_time(duration) + " ago")
+    } else {
+        published
+            .duration_since(now)
+            .map(|duration| "in ".to_string() + &format_rel_time(duration))
+    }
+}
+
 pub fn format_semi_engineering(value: f32) -> String {
     static SUFFIXES: [char; 5] = [' ', 'k', 'M', 'B', 'T'];
     let Some(suffix) = (0..)
@@ -40,8 +62,49 @@ pub(crate) fn cache() -> &'static DewCache {
     APP_CACHE.get_or_init(DewCache::default)
 }
 
+pub(crate) fn progress() -> &'static DewProgress {
+    static APP_PROGRESS: OnceCell<DewProgress> = OnceCell::new();
+    APP_PROGRESS.get_or_init(DewProgress::default)
+}
+
 pub(crate) fn cache_dir(fname: &Path) -> PathBuf {
     let mut dir = cache().dir().clone();
     dir.push(fname);
     dir
 }
+
+/// Where the app keeps the state file `fname`, like "feed.json".
+pub(crate) fn data_path(fname: &str) -> PathBuf {
+    user_data_dir().join("DewDuct/").join(fname)
+}
+
+/// The state stored at `path`, or the default if there is none yet or it
+/// can't be parsed.
+pub(crate) fn load_state<T: DeserializeOwned + Default>(path: &Path) -> T {
+    let Ok(contents) = read(path) else {
+        return T::default();
+    };
+    serde_json::from_slice(&contents).unwrap_or_else(|err| {
+        g_warning!(
+            "DewUtil",
+            "malformed state file {}: {}",
+            path.display(),
+            err
+        );
+        T::default()
+    })
+}
+
+pub(crate) fn store_state<T: Serialize>(
+    path: &Path,
+    state: &T,
+) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = File::create(path).with_context(|| {
+        format!("unable to create file {}", path.display())
+    })?;
+    serde_json::to_writer(file, state)
+        .with_context(|| format!("unable to write {}", path.display()))
+}
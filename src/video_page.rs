@@ -18,23 +18,34 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
-use std::{
-    cell::RefCell,
-    process::{Child, Command},
-    rc::Rc,
-};
+use std::{cell::RefCell, process::Command, rc::Rc};
 
 #[allow(unused_imports)]
 use adw::{prelude::*, subclass::prelude::*};
-use glib::g_warning;
+use glib::{clone, g_warning};
 use gtk::{gio, glib};
 #[allow(unused_imports)]
 use gtk::{prelude::*, subclass::prelude::*};
 
+use futures::{channel::mpsc, StreamExt};
+use invidious::hidden::Caption;
 use invidious::video::Video;
 
+use crate::chapters::{self, Chapter};
+use crate::comments_view::DewCommentsView;
+use crate::description::{self, DescriptionLink};
+use crate::downloads::DownloadSource;
+use crate::embedded_player::DewEmbeddedPlayer;
 use crate::format_semi_engineering;
+use crate::player::{DewMpv, MpvEvent};
+use crate::player_bar::Track;
+use crate::progress::{store_progress, ProgressTracker, WatchProgress};
+use crate::quality::{self, Quality};
+use crate::settings::{Downloader, PlaybackSource, PlayerKind};
 use crate::thumbnail::DewThumbnail;
+use crate::util::progress;
+use crate::window::DewDuctWindow;
+use crate::yt_item_list::{DewYtItem, DewYtItemList};
 
 mod imp {
     use super::*;
@@ -44,8 +55,18 @@ mod imp {
     pub struct DewVideoPage {
         // Template widgets
         #[template_child]
+        player_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
         vid_thumbnail: TemplateChild<DewThumbnail>,
         #[template_child]
+        embedded_player: TemplateChild<DewEmbeddedPlayer>,
+        #[template_child]
+        start_over_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        quality_dropdown: TemplateChild<gtk::DropDown>,
+        #[template_child]
+        captions_dropdown: TemplateChild<gtk::DropDown>,
+        #[template_child]
         title: TemplateChild<gtk::Label>,
         // #[template_child]
         // author_thumb: TemplateChild<gtk::Image>,
@@ -57,14 +78,30 @@ mod imp {
         views: TemplateChild<gtk::Label>,
         #[template_child]
         likes: TemplateChild<gtk::Label>,
-        // #[template_child]
-        // bottom_stack: TemplateChild<adw::ViewStack>,
+        #[template_child]
+        bottom_stack: TemplateChild<adw::ViewStack>,
         #[template_child]
         description: TemplateChild<gtk::Label>,
+        #[template_child]
+        chapters_page: TemplateChild<adw::ViewStackPage>,
+        #[template_child]
+        chapter_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        comments_view: TemplateChild<DewCommentsView>,
+        #[template_child]
+        related_list: TemplateChild<DewYtItemList>,
         // #[template_child]
         // bottom_switcher: TemplateChild<adw::ViewSwitcherBar>,
         vid: RefCell<Option<Video>>,
-        mpv_child: Rc<RefCell<Option<Child>>>,
+        /// The instance `vid` came from; its relative URLs point there.
+        instance: RefCell<String>,
+        mpv: RefCell<Option<Rc<DewMpv>>>,
+        /// What the quality dropdown offers, in the same order.
+        qualities: RefCell<Vec<Quality>>,
+        /// Of the video, in the same order as in the list.
+        chapters: RefCell<Vec<Chapter>>,
+        /// What the captions dropdown offers, after "Off".
+        captions: RefCell<Vec<Caption>>,
     }
 
     #[glib::object_subclass]
@@ -75,7 +112,7 @@ mod imp {
 
         fn class_init(klass: &mut Self::Class) {
             klass.bind_template();
-            // klass.bind_template_callbacks();
+            klass.bind_template_callbacks();
         }
 
         fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
@@ -87,21 +124,41 @@ mod imp {
         fn constructed(&self) {
             self.vid.take();
             {
-                // let's spawn mpv when thumbnail is clicked!
+                // let's play when thumbnail is clicked!
                 let click = gtk::GestureClick::new();
 
                 let page = self.obj().clone();
                 click.connect_pressed(move |_, _n, _x, _y| {
-                    page.imp().play_mpv()
+                    glib::spawn_future_local(clone!(@weak page =>
+                        async move { page.imp().play(false).await; }));
                 });
 
                 self.vid_thumbnail.add_controller(click);
             }
+            self.description.connect_activate_link(clone!(
+                @weak self as page =>
+                @default-return glib::Propagation::Proceed,
+                move |_, uri| {
+                    page.activate_link(uri);
+                    glib::Propagation::Stop
+                }
+            ));
+            // comments are only loaded when someone looks at them.
+            self.bottom_stack.connect_visible_child_name_notify(
+                clone!(@weak self as page => move |_| {
+                    glib::spawn_future_local(
+                        clone!(@weak page => async move {
+                            page.load_visible_tab().await
+                        }),
+                    );
+                }),
+            );
         }
     }
     impl WidgetImpl for DewVideoPage {}
     impl BoxImpl for DewVideoPage {}
 
+    #[gtk::template_callbacks]
     impl DewVideoPage {
         fn id(&self) -> Option<String> {
             self.vid
@@ -109,33 +166,435 @@ mod imp {
                 .ok()
                 .and_then(|x| x.as_ref().map(|vid| vid.id.clone()))
         }
-        fn play_mpv(&self) {
-            let id = self.id();
-            let mpv_child = self.mpv_child.clone();
+        fn window(&self) -> DewDuctWindow {
+            self.obj().root().and_downcast().unwrap()
+        }
+        #[template_callback]
+        async fn start_over(&self) {
+            self.play(true).await
+        }
+        #[template_callback]
+        async fn listen(&self) {
+            let Some(track) = self.to_bar() else { return };
+            self.window().play_audio(vec![track]).await;
+        }
+        #[template_callback]
+        async fn popup(&self) {
+            let Some(track) = self.to_bar() else { return };
+            self.window().play_popup(vec![track]).await;
+        }
+        #[template_callback]
+        fn download(&self) {
+            let Some(vid) = self.vid.borrow().clone() else {
+                return;
+            };
+            let window = self.window();
+            let settings = window.settings();
+            let selected = self.quality_dropdown.selected() as usize;
+            let quality = self.qualities.borrow().get(selected).cloned();
 
-            let Some(id) = id.as_ref() else { return };
+            let source = match settings.downloader {
+                Downloader::Ytdl => DownloadSource::Ytdl {
+                    format: match quality {
+                        Some(quality) => quality.ytdl_format,
+                        None => quality::fallback_ytdl_format(
+                            settings.default_quality,
+                        ),
+                    },
+                },
+                Downloader::Builtin => {
+                    let instance = self.instance.borrow().clone();
+                    let max_height = quality
+                        .map(|quality| quality.height)
+                        .or(settings.default_quality);
+                    let Some((url, container)) =
+                        quality::muxed_stream(&vid, &instance, max_height)
+                    else {
+                        window.toast(
+                            "This video can only be downloaded with yt-dlp",
+                        );
+                        return;
+                    };
+                    DownloadSource::Direct {
+                        url,
+                        file_name: format!("{}.{container}", vid.id),
+                    }
+                }
+            };
+            let captions = match settings.download_captions {
+                true => self.picked_captions(),
+                false => None,
+            };
+            window.download(&DewYtItem::from(vid), source, captions);
+        }
+        /// The language and the URL of the captions picked in the
+        /// dropdown, if any.
+        fn picked_captions(&self) -> Option<(String, String)> {
+            let selected = self.captions_dropdown.selected() as usize;
+            let captions = self.captions.borrow();
+            let caption = captions.get(selected.checked_sub(1)?)?;
+            let instance = self.instance.borrow();
+            let language = match caption.language.is_empty() {
+                true => caption.label.clone(),
+                false => caption.language.clone(),
+            };
+            Some((language, quality::resolve_url(&caption.url, &instance)))
+        }
+        /// Stops the video here, for it to play in the player bar.
+        fn to_bar(&self) -> Option<Track> {
+            let vid = self.vid.borrow().clone()?;
+            self.window().add_to_history(&DewYtItem::from(vid.clone()));
+            self.stop_video();
+            Some(Track {
+                id: vid.id,
+                title: vid.title,
+                captions: self.picked_captions().map(|(_, url)| url),
+            })
+        }
+        #[template_callback]
+        async fn chapter_activated(&self, row: &gtk::ListBoxRow) {
+            let start = self
+                .chapters
+                .borrow()
+                .get(row.index() as usize)
+                .map(|chapter| chapter.start);
+            if let Some(start) = start {
+                self.seek(start as f64).await;
+            }
+        }
+        fn fill_chapters(&self, description: &str) {
+            let chapters = chapters::parse_chapters(description);
+            while let Some(row) = self.chapter_list.first_child() {
+                self.chapter_list.remove(&row);
+            }
+            for chapter in &chapters {
+                let row = adw::ActionRow::builder()
+                    .title(glib::markup_escape_text(&chapter.title))
+                    .subtitle(chapters::format_timestamp(chapter.start))
+                    .activatable(true)
+                    .build();
+                row.add_suffix(&gtk::Image::from_icon_name(
+                    "media-playback-start-symbolic",
+                ));
+                self.chapter_list.append(&row);
+            }
+            self.chapters_page.set_visible(!chapters.is_empty());
+            self.chapters.replace(chapters);
+        }
+        /// For the links in the description.
+        fn activate_link(&self, uri: &str) {
+            let Some(id) = self.id() else { return };
+            match DescriptionLink::classify(uri, &id) {
+                DescriptionLink::Seek(position) => {
+                    glib::spawn_future_local(
+                        clone!(@weak self as page => async move {
+                            page.seek(position as f64).await
+                        }),
+                    );
+                }
+                DescriptionLink::Video(id) => {
+                    WidgetExt::activate_action(
+                        &*self.obj(),
+                        "win.play",
+                        Some(&Some(id).to_variant()),
+                    )
+                    .expect("the action win.play does not exist");
+                }
+                DescriptionLink::Channel(id) => {
+                    let window = self.window();
+                    glib::spawn_future_local(async move {
+                        window.show_channel(&id).await
+                    });
+                }
+                DescriptionLink::External(uri) => {
+                    gtk::UriLauncher::new(&uri).launch(
+                        Some(&self.window()),
+                        gio::Cancellable::NONE,
+                        move |result| {
+                            if let Err(err) = result {
+                                g_warning!(
+                                    "DewVideoPage",
+                                    "can't open {}: {}",
+                                    uri,
+                                    err
+                                );
+                            }
+                        },
+                    );
+                }
+            }
+        }
+        /// Jumps to `position` seconds in, in whichever player is playing
+        /// the video; or starts playing it from there.
+        async fn seek(&self, position: f64) {
+            let mpv = self.mpv.borrow().clone();
+            if let Some(mpv) = mpv {
+                match mpv.seek(position).await {
+                    Ok(()) => return,
+                    // mpv was closed, it seems.
+                    Err(err) => g_warning!(
+                        "DewVideoPage",
+                        "can't seek in mpv: {}",
+                        err
+                    ),
+                }
+            }
+            if self.embedded_player.seek(position) {
+                return;
+            }
+            let Some(id) = self.id() else { return };
+            self.play_from(id, Some(position)).await
+        }
+        /// Stops the video, which is stopped once dropped for mpv.
+        fn stop_video(&self) {
+            self.mpv.take();
+            self.embedded_player.stop();
+            self.player_stack.set_visible_child(&*self.vid_thumbnail);
+        }
+        /// Continues from where the user stopped last time, unless
+        /// `start_over`, with the player from the settings.
+        async fn play(&self, start_over: bool) {
+            let Some(id) = self.id() else { return };
+            let start = match start_over {
+                true => None,
+                false => progress()
+                    .get(&id)
+                    .and_then(|watched| watched.resume_position()),
+            };
+            self.play_from(id, start).await
+        }
+        async fn play_from(&self, id: String, start: Option<f64>) {
+            if let Some(vid) = self.vid.borrow().clone() {
+                self.window().add_to_history(&DewYtItem::from(vid));
+            }
+            // only one thing playing at a time.
+            self.window().stop_audio();
+            self.stop_video();
 
-            let url = format!("https://youtube.com/watch?v={}", id);
+            match self.window().settings().player {
+                PlayerKind::Mpv => self.play_mpv(id, start).await,
+                PlayerKind::Embedded => self.play_embedded(id, start),
+            }
+        }
+        fn play_embedded(&self, id: String, start: Option<f64>) {
+            let Some(url) = self.embeddable_url() else {
+                self.window()
+                    .toast("This video can only be played with mpv");
+                return;
+            };
+            let media = self.embedded_player.play(&url, start);
+            self.player_stack.set_visible_child(&*self.embedded_player);
+
+            media.connect_timestamp_notify(
+                clone!(@weak self as page => move |media| {
+                    let watched = WatchProgress {
+                        position: media.timestamp() as f64 / 1e6,
+                        length: media.duration() as f64 / 1e6,
+                    };
+                    progress().set(&id, watched);
+                    page.update_resume();
+                }),
+            );
+            media.connect_playing_notify(|media| {
+                if !media.is_playing() {
+                    store_progress();
+                }
+            });
+        }
+        /// The embedded player needs audio and video in the same stream;
+        /// if the selected quality is not like that, the closest one below
+        /// it, or just the closest one.
+        fn embeddable_url(&self) -> Option<String> {
+            let qualities = self.qualities.borrow();
+            let selected = self.quality_dropdown.selected() as usize;
+            let max_height =
+                qualities.get(selected).map_or(u32::MAX, |q| q.height);
+            let muxed = || qualities.iter().filter(|q| q.urls.len() == 1);
+
+            muxed()
+                .find(|quality| quality.height <= max_height)
+                .or_else(|| muxed().next_back())
+                .map(|quality| quality.urls[0].clone())
+        }
+        async fn play_mpv(&self, id: String, start: Option<f64>) {
             let mut mpv = Command::new("mpv");
-            mpv.arg(url).arg("--ytdl-format=best[height<=480]");
-            g_warning!(
-                "DewVideoPage",
-                "running... {:?} {:?}",
-                mpv.get_program(),
-                mpv.get_args().collect::<Vec<_>>()
+            self.add_streams(&mut mpv, &id);
+            if let Some((_, url)) = self.picked_captions() {
+                mpv.arg(format!("--sub-file={url}"));
+            }
+            if let Some(start) = start {
+                mpv.arg(format!("--start={start:.0}"));
+            }
+
+            let mpv = match DewMpv::spawn(mpv).await {
+                Ok(mpv) => Rc::new(mpv),
+                Err(err) => {
+                    g_warning!(
+                        "DewVideoPage",
+                        "can't play {}: {}",
+                        id,
+                        err
+                    );
+                    self.window().toast(&err.to_string());
+                    return;
+                }
+            };
+            for property in ["time-pos", "duration"] {
+                if let Err(err) = mpv.observe(property).await {
+                    g_warning!(
+                        "DewVideoPage",
+                        "can't observe mpv: {}",
+                        err
+                    );
+                }
+            }
+            let events = mpv.events();
+            self.mpv.replace(Some(mpv));
+
+            if let Some(events) = events {
+                self.follow_playback(id, events).await;
+            }
+        }
+
+        /// Tells mpv what to play, in the quality selected in the
+        /// dropdown.
+        fn add_streams(&self, mpv: &mut Command, id: &str) {
+            if let Some(file) = self.window().downloaded_file(id) {
+                mpv.arg(file);
+                return;
+            }
+            let settings = self.window().settings();
+            let selected = self.quality_dropdown.selected() as usize;
+            let qualities = self.qualities.borrow();
+            let quality = qualities.get(selected);
+
+            if settings.playback_source == PlaybackSource::Ytdl {
+                let ytdl_format = match quality {
+                    Some(quality) => quality.ytdl_format.clone(),
+                    None => quality::fallback_ytdl_format(
+                        settings.default_quality,
+                    ),
+                };
+                mpv.arg(format!("https://youtube.com/watch?v={id}"))
+                    .arg(format!("--ytdl-format={ytdl_format}"));
+                return;
+            }
+
+            mpv.arg("--ytdl=no");
+            match quality.map(|quality| quality.urls.as_slice()) {
+                Some([video, audio @ ..]) => {
+                    mpv.arg(video);
+                    for audio in audio {
+                        mpv.arg(format!("--audio-file={audio}"));
+                    }
+                }
+                // no formats, like for live streams; the manifest still
+                // has them.
+                _ => {
+                    let dash = self
+                        .vid
+                        .borrow()
+                        .as_ref()
+                        .map(|vid| vid.dash.clone())
+                        .unwrap_or_default();
+                    let instance = self.instance.borrow();
+                    mpv.arg(quality::resolve_url(&dash, &instance));
+                }
+            }
+        }
+        fn fill_qualities(&self, vid: &Video) {
+            let instance = self.instance.borrow();
+            let qualities = quality::qualities(vid, &instance);
+            let labels: Vec<&str> = qualities
+                .iter()
+                .map(|quality| quality.label.as_str())
+                .collect();
+            let preferred = quality::preferred(
+                &qualities,
+                self.window().settings().default_quality,
             );
 
-            // spawn child process
-            let mpv_process = mpv.spawn().expect("mpv not found");
-            let prev_mpv = mpv_child.replace(Some(mpv_process));
+            let dropdown = &self.quality_dropdown;
+            dropdown.set_model(Some(&gtk::StringList::new(&labels)));
+            dropdown.set_selected(preferred as u32);
+            dropdown.set_visible(!qualities.is_empty());
+            self.qualities.replace(qualities);
+        }
+        /// Off, then every track; the one in the preferred language is
+        /// picked.
+        fn fill_captions(&self, vid: &Video) {
+            let preferred = self.window().settings().captions_language;
+            let labels: Vec<&str> = std::iter::once("No Captions")
+                .chain(vid.captions.iter().map(|c| c.label.as_str()))
+                .collect();
+            let find = |matches: &dyn Fn(&str) -> bool| {
+                vid.captions
+                    .iter()
+                    .position(|caption| matches(&caption.language))
+            };
+            let picked = preferred
+                .and_then(|preferred| {
+                    find(&|language| {
+                        language.eq_ignore_ascii_case(&preferred)
+                    })
+                    .or_else(|| {
+                        find(&|language| same_base(language, &preferred))
+                    })
+                })
+                .map_or(0, |i| i + 1);
 
-            // if there was already a mpv instance running...
-            if let Some(mut prev_mpv) = prev_mpv {
-                prev_mpv.kill().expect("error killing it");
+            let dropdown = &self.captions_dropdown;
+            dropdown.set_model(Some(&gtk::StringList::new(&labels)));
+            dropdown.set_selected(picked as u32);
+            // the embedded player can't show them; what is picked is still
+            // saved with downloads.
+            let embedded =
+                self.window().settings().player == PlayerKind::Embedded;
+            dropdown.set_visible(!vid.captions.is_empty() && !embedded);
+            self.captions.replace(vid.captions.clone());
+        }
+
+        /// Remembers how far the user got, until mpv exits.
+        async fn follow_playback(
+            &self,
+            id: String,
+            mut events: mpsc::UnboundedReceiver<MpvEvent>,
+        ) {
+            let mut tracker = ProgressTracker::new(id);
+            while let Some(event) = events.next().await {
+                let MpvEvent::PropertyChange { name, data } = event else {
+                    continue;
+                };
+                let Some(data) = data.as_f64() else { continue };
+                let updated = tracker.update(&name, data);
+                if updated && self.id().as_deref() == Some(tracker.id()) {
+                    self.update_resume();
+                }
             }
         }
 
-        pub(crate) async fn set_vid(&self, new_vid: Video) {
+        async fn load_visible_tab(&self) {
+            if self.bottom_stack.visible_child_name().as_deref()
+                == Some("comments")
+            {
+                self.comments_view.load().await;
+            }
+        }
+
+        /// Shows the saved progress, and if it can be resumed.
+        fn update_resume(&self) {
+            let watched = self.id().and_then(|id| progress().get(&id));
+            let watched = watched.unwrap_or_default();
+            self.vid_thumbnail.set_progress(watched.fraction());
+            self.start_over_button
+                .set_visible(watched.resume_position().is_some());
+        }
+
+        pub(crate) async fn set_vid(
+            &self,
+            new_vid: Video,
+            instance: String,
+        ) {
             if !self
                 .vid
                 .borrow()
@@ -148,6 +607,10 @@ mod imp {
                     self.id(),
                     Some(&new_vid.id)
                 );
+                // mpv can keep playing in its own window, but the
+                // embedded player shows the old video.
+                self.embedded_player.stop();
+                self.player_stack.set_visible_child(&*self.vid_thumbnail);
 
                 let Video {
                     id,
@@ -156,9 +619,11 @@ mod imp {
                     author,
                     title,
                     description,
+                    description_html,
                     likes,
                     views,
                     sub_count_text,
+                    recommended_videos,
                     ..
                 } = &new_vid;
 
@@ -172,15 +637,28 @@ mod imp {
                 );
                 self.sub_count
                     .set_text(&format!("{} subscribers", sub_count_text));
-                // self.description.set_markup(&new_vid.description_html);
-                self.description.set_text(description);
+                self.instance.replace(instance);
+                self.fill_qualities(&new_vid);
+                self.fill_captions(&new_vid);
+                self.description.set_markup(&description::to_markup(
+                    description_html,
+                    id,
+                ));
+                self.fill_chapters(description);
+                self.related_list.set_from_vec(
+                    recommended_videos
+                        .iter()
+                        .cloned()
+                        .map(DewYtItem::from)
+                        .collect(),
+                );
 
                 self.vid_thumbnail
                     .update_from_params(
                         id.clone(),
                         thumbnails.iter(),
                         *length as u64,
-                        0.0f64,
+                        progress().fraction(id),
                     )
                     .await
                     .unwrap_or_else(|err| {
@@ -191,7 +669,10 @@ mod imp {
                             err
                         )
                     });
+                self.comments_view.set_video_id(id);
                 self.vid.replace(Some(new_vid));
+                self.update_resume();
+                self.load_visible_tab().await;
             } else {
                 g_warning!("DewVideoPage", "clicked on the same vid...")
             }
@@ -216,3 +697,15 @@ glib::wrapper! {
         @extends gtk::Widget, gtk::Box,
         @implements gio::ActionGroup, gio::ActionMap;
 }
+
+/// If two language codes are of the same language, like `pt` and
+/// `pt-BR`.
+fn same_base(language: &str, other: &str) -> bool {
+    let base = |code: &str| {
+        code.split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase()
+    };
+    base(language) == base(other)
+}
@@ -18,8 +18,6 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-
 #[allow(unused_imports)]
 use adw::{prelude::*, subclass::prelude::*};
 use glib::g_warning;
@@ -49,6 +47,10 @@ mod imp {
         #[template_child]
         pub(super) published: TemplateChild<gtk::Label>,
         #[template_child]
+        pub(super) new_badge: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub(super) menu_button: TemplateChild<gtk::MenuButton>,
+        #[template_child]
         pub(crate) thumbnail: TemplateChild<DewThumbnail>,
     }
 
@@ -98,7 +100,7 @@ impl DewVideoRow {
         T: Clone + 'a,
         crate::yt_item_list::Thumbnail: From<T>,
     {
-        let watched_progress: f64 = 0.; // todo!
+        let watched_progress = util::progress().fraction(&id);
 
         self.imp().title.set_text(&title);
         self.imp().channel.set_text(&author);
@@ -112,25 +114,32 @@ impl DewVideoRow {
     }
 
     fn set_published(&self, published: u64) {
-        let now = SystemTime::now();
-        let published: SystemTime =
-            UNIX_EPOCH + Duration::from_secs(published);
-        let rel_upload_time = if now > published {
-            now.duration_since(published)
-                .map(|duration| util::format_rel_time(duration) + " ago")
-        } else {
-            published.duration_since(now).map(|duration| {
-                "in ".to_string() + &util::format_rel_time(duration)
-            })
-        };
-        let Ok(rel_upload_time) = rel_upload_time else {
-            g_warning!("DewVideoRow", "{}", rel_upload_time.unwrap_err());
+        // not known, like for related videos.
+        if published == 0 {
+            self.imp().published.set_text("");
             return;
+        }
+        let rel_upload_time = match util::format_published(published) {
+            Ok(rel_upload_time) => rel_upload_time,
+            Err(err) => {
+                g_warning!("DewVideoRow", "{}", err);
+                return;
+            }
         };
 
         self.imp().published.set_text(&rel_upload_time);
     }
 
+    pub(crate) fn set_new(&self, is_new: bool) {
+        self.imp().new_badge.set_visible(is_new);
+    }
+
+    /// The button is hidden when there is no menu.
+    pub(crate) fn set_menu(&self, menu: Option<&gio::MenuModel>) {
+        self.imp().menu_button.set_menu_model(menu);
+        self.imp().menu_button.set_visible(menu.is_some());
+    }
+
     fn set_views(&self, views: u64) {
         self.imp().views.set_text(
             &(util::format_semi_engineering(views as f32) + " views"),
@@ -19,6 +19,7 @@
  */
 
 use std::cell::RefCell;
+use std::path::PathBuf;
 use std::rc::Rc;
 
 #[allow(unused_imports)]
@@ -29,12 +30,26 @@ use gtk::{gio, glib};
 use gtk::{prelude::*, subclass::prelude::*};
 
 use crate::{
-    channel_page::DewChannelPage, popular_page::DewPopularPage,
-    search_page::DewSearchPage, subscriptions_page::DewSubscriptionsPage,
+    channel_page::DewChannelPage,
+    downloads::DownloadSource,
+    downloads_page::DewDownloadsPage,
+    feed_page::DewFeedPage,
+    history_page::DewHistoryPage,
+    instances::{discover_cached, DewInstancePool, RankedInstance},
+    library_page::DewLibraryPage,
+    player_bar::{BarMode, DewPlayerBar, Track},
+    playlist_page::DewPlaylistPage,
+    playlists_page::DewPlaylistsPage,
+    popular_page::DewPopularPage,
+    queue_page::DewQueuePage,
+    search_page::DewSearchPage,
+    settings::{DewSettings, PlaybackSource, KNOWN_INSTANCES},
+    subscriptions_page::DewSubscriptionsPage,
     video_page::DewVideoPage,
+    yt_item_list::DewYtItem,
 };
 
-use invidious::{ClientAsyncTrait, ClientSync};
+use invidious::ClientAsyncTrait;
 use tokio::runtime::Runtime;
 
 mod imp {
@@ -55,13 +70,33 @@ mod imp {
         #[template_child]
         popular_page: TemplateChild<DewPopularPage>,
         #[template_child]
+        feed_page: TemplateChild<DewFeedPage>,
+        #[template_child]
         subscriptions_page: TemplateChild<DewSubscriptionsPage>,
         #[template_child]
+        history_page: TemplateChild<DewHistoryPage>,
+        #[template_child]
+        pub(super) queue_page: TemplateChild<DewQueuePage>,
+        #[template_child]
+        pub(super) playlists_page: TemplateChild<DewPlaylistsPage>,
+        #[template_child]
+        playlist_page: TemplateChild<DewPlaylistPage>,
+        #[template_child]
+        pub(super) library_page: TemplateChild<DewLibraryPage>,
+        #[template_child]
+        downloads_page: TemplateChild<DewDownloadsPage>,
+        #[template_child]
+        pub(super) player_bar: TemplateChild<DewPlayerBar>,
+        #[template_child]
         search_bar: TemplateChild<gtk::SearchBar>,
         #[template_child]
         nav_view: TemplateChild<adw::NavigationView>,
+        #[template_child]
+        pub(super) toast_overlay: TemplateChild<adw::ToastOverlay>,
         _last_visible_page: Rc<RefCell<Option<GString>>>,
-        pub(super) invidious_client: RefCell<ClientSync>,
+        pub(super) instance_pool: DewInstancePool,
+        pub(super) ranked_instances: RefCell<Vec<RankedInstance>>,
+        pub(super) settings: RefCell<DewSettings>,
 
         pub(super) tokio_rt: RefCell<Option<Runtime>>,
     }
@@ -92,8 +127,13 @@ mod imp {
     impl ObjectImpl for DewDuctWindow {
         fn constructed(&self) {
             self.parent_constructed();
-            self.invidious_client.borrow_mut().instance =
-                "https://invidious.fdn.fr".into();
+            let settings = DewSettings::load();
+            self.instance_pool.set_preferred(settings.instance.clone());
+            self.instance_pool
+                .add_fallbacks(KNOWN_INSTANCES.iter().copied());
+            self.settings.replace(settings);
+            glib::spawn_future_local(clone!(@weak self as win =>
+                async move { win.discover_instances().await; }));
 
             self.search_bar.set_key_capture_widget(Some(&*self.obj()));
             self.search_bar
@@ -156,25 +196,30 @@ mod imp {
             };
 
             let vid_page = &self.video_page;
-            let invidious = self.obj().async_invidious_client();
+            let pool = self.obj().instance_pool();
+            // proxied stream URLs are only given when asked for.
+            let params = match self.settings.borrow().playback_source {
+                PlaybackSource::Proxied => Some("local=true"),
+                _ => None,
+            };
 
             let vid = self
                 .obj()
                 .spawn(async move {
-                    invidious.video(&id, None).await.map_err(|err| {
-                        g_warning!("DewWindow", "cant load {id}: {err:#?}");
-                        g_warning!(
-                            "DewWindow",
-                            "the instance used was {}",
-                            invidious.instance
-                        );
+                    pool.request_from(|invidious| {
+                        let id = id.clone();
+                        async move { invidious.video(&id, params).await }
+                    })
+                    .await
+                    .map_err(|err| {
+                        g_warning!("DewWindow", "cant load video: {err:#}");
                     })
                 })
                 .await;
 
-            let Ok(Ok(vid)) = vid else { return };
+            let Ok(Ok((vid, instance))) = vid else { return };
 
-            vid_page.imp().set_vid(vid).await;
+            vid_page.imp().set_vid(vid, instance).await;
             self.screen_stack.set_visible_child_name("video_page");
             self.nav_view.pop_to_tag("main_view");
             self.search_page.search_entry().emit_stop_search();
@@ -185,6 +230,9 @@ mod imp {
         ) -> glib::signal::SignalHandlerId {
             self.subscriptions_page.imp().connect_subs_changed(f)
         }
+        pub fn subscriptions(&self) -> Vec<DewYtItem> {
+            self.subscriptions_page.imp().subscriptions()
+        }
         pub async fn subscribe(
             &self,
             channel_id: String,
@@ -197,6 +245,109 @@ mod imp {
         pub fn unsubscribe(&self, channel_id: String) {
             self.subscriptions_page.imp().del_subscription(channel_id)
         }
+        pub fn add_to_history(&self, item: &DewYtItem) {
+            self.history_page.imp().add(item)
+        }
+        pub(super) fn enqueue(&self, items: &[DewYtItem]) {
+            self.queue_page.imp().add(items);
+            let toast = match items {
+                [item] => format!(
+                    "“{}” added to the queue",
+                    glib::markup_escape_text(&item.title())
+                ),
+                items => {
+                    format!("{} videos added to the queue", items.len())
+                }
+            };
+            self.obj().toast(&toast);
+        }
+        pub(super) fn download(
+            &self,
+            item: &DewYtItem,
+            source: DownloadSource,
+            captions: Option<(String, String)>,
+        ) {
+            let title = glib::markup_escape_text(&item.title());
+            if !self.downloads_page.imp().add(item, source, captions) {
+                self.obj().toast(&format!(
+                    "“{title}” is already downloading with other \
+                     settings, cancel it first"
+                ));
+                return;
+            }
+            self.obj().toast(&format!("Downloading “{title}”"));
+        }
+        pub(super) fn show_downloads(&self) {
+            self.nav_view.push_by_tag("downloads_page");
+        }
+        pub(super) fn show_local_playlist(&self, id: &str) {
+            let Some(playlist) = self.playlists_page.imp().get(id) else {
+                return;
+            };
+            self.playlist_page.show_local(playlist);
+            self.nav_view.push_by_tag("playlist_page");
+        }
+        pub(super) async fn show_playlist(&self, id: &str) {
+            let pool = self.obj().instance_pool();
+            let id = id.to_owned();
+            let playlist = self
+                .obj()
+                .spawn(async move {
+                    pool.request(|invidious| {
+                        let id = id.clone();
+                        async move { invidious.playlist(&id, None).await }
+                    })
+                    .await
+                })
+                .await;
+            let playlist = match playlist {
+                Ok(Ok(playlist)) => playlist,
+                Ok(Err(err)) => {
+                    g_warning!("DewWindow", "cant load playlist: {err:#}");
+                    self.obj().toast("Can't load the playlist");
+                    return;
+                }
+                Err(err) => {
+                    g_warning!("DewWindow", "{err}");
+                    return;
+                }
+            };
+            self.playlist_page.show_remote(
+                &playlist.title,
+                &playlist.author,
+                playlist.videos.into_iter().map(DewYtItem::from).collect(),
+            );
+            self.nav_view.push_by_tag("playlist_page");
+        }
+        pub(super) async fn play_queue(&self, mode: BarMode) {
+            let Some(item) = self.queue_page.imp().pop_front() else {
+                return;
+            };
+            self.add_to_history(&item);
+            self.player_bar.play(vec![(&item).into()], mode).await;
+        }
+        async fn discover_instances(&self) {
+            match self.obj().spawn(discover_cached()).await {
+                Ok(Ok(ranked)) => {
+                    self.instance_pool.rank_fallbacks(
+                        ranked.iter().map(|instance| instance.url.as_str()),
+                    );
+                    self.ranked_instances.replace(ranked);
+                }
+                Ok(Err(err)) => g_warning!(
+                    "DewWindow",
+                    "can't rank the public instances: {:#}",
+                    err
+                ),
+                Err(err) => {
+                    g_warning!(
+                        "DewWindow",
+                        "this should not crash: {}",
+                        err
+                    )
+                }
+            }
+        }
         pub(super) fn set_tokio_rt(&self, tokio_rt: Option<Runtime>) {
             self.tokio_rt.replace(tokio_rt);
         }
@@ -230,15 +381,41 @@ impl DewDuctWindow {
         self.imp().search_started();
     }
     pub fn invidious_client(&self) -> invidious::ClientSync {
-        self.imp().invidious_client.borrow().clone()
-    }
-    pub fn async_invidious_client(&self) -> invidious::ClientAsync {
-        let inv = self.imp().invidious_client.borrow();
-        invidious::ClientAsync {
-            instance: inv.instance.clone(),
+        invidious::ClientSync {
+            instance: self.imp().instance_pool.instance(),
             ..Default::default()
         }
     }
+    /// For errors the user should know about.
+    pub fn toast(&self, message: &str) {
+        self.imp().toast_overlay.add_toast(adw::Toast::new(message));
+    }
+    pub fn instance_pool(&self) -> DewInstancePool {
+        self.imp().instance_pool.clone()
+    }
+    /// The public instances that answered, fastest first. Empty until the
+    /// probing is done.
+    pub fn ranked_instances(&self) -> Vec<RankedInstance> {
+        self.imp().ranked_instances.borrow().clone()
+    }
+    pub fn set_instance(&self, instance: String) {
+        g_warning!("DewWindow", "switching to instance {}", &instance);
+        self.imp().instance_pool.set_preferred(instance.clone());
+        self.update_settings(|settings| settings.instance = instance);
+    }
+    pub fn settings(&self) -> DewSettings {
+        self.imp().settings.borrow().clone()
+    }
+    pub fn update_settings(&self, f: impl FnOnce(&mut DewSettings)) {
+        let mut settings = self.imp().settings.borrow_mut();
+        f(&mut settings);
+        if let Err(err) = settings.store() {
+            g_warning!("DewWindow", "can't store settings: {:#}", err);
+        }
+    }
+    pub async fn show_channel(&self, id: &str) {
+        self.imp().show_channel(id).await
+    }
     pub async fn show_channel_yt_item(
         &self,
         channel: &crate::yt_item_list::DewYtItem,
@@ -251,6 +428,9 @@ impl DewDuctWindow {
     ) -> anyhow::Result<()> {
         self.imp().subscribe(channel_id).await
     }
+    pub fn subscriptions(&self) -> Vec<DewYtItem> {
+        self.imp().subscriptions()
+    }
     pub fn unsubscribe(&self, channel_id: String) {
         self.imp().unsubscribe(channel_id)
     }
@@ -260,6 +440,84 @@ impl DewDuctWindow {
     ) -> glib::signal::SignalHandlerId {
         self.imp().connect_subs_changed(f)
     }
+    pub fn add_to_history(&self, item: &DewYtItem) {
+        self.imp().add_to_history(item)
+    }
+    /// In the background, with the controls at the bottom of the window.
+    pub async fn play_audio(&self, tracks: Vec<Track>) {
+        self.imp().player_bar.play(tracks, BarMode::Audio).await
+    }
+    pub async fn play_popup(&self, tracks: Vec<Track>) {
+        self.imp().player_bar.play(tracks, BarMode::Popup).await
+    }
+    pub async fn play_video(&self, tracks: Vec<Track>) {
+        self.imp().player_bar.play(tracks, BarMode::Video).await
+    }
+    /// Stops the audio or the popup, whichever is playing.
+    pub fn stop_audio(&self) {
+        self.imp().player_bar.stop()
+    }
+    /// Adds the videos at the end of the queue.
+    pub fn enqueue(&self, items: &[DewYtItem]) {
+        self.imp().enqueue(items)
+    }
+    /// Plays the next video in the queue, taking it out of there.
+    pub async fn play_queue(&self, mode: BarMode) {
+        self.imp().play_queue(mode).await
+    }
+    /// Like `play_queue`, unless something is playing already; then the
+    /// queue goes on after it.
+    pub async fn start_queue(&self, mode: BarMode) {
+        if !self.imp().player_bar.is_playing() {
+            self.play_queue(mode).await
+        }
+    }
+    /// `captions` are the language and the URL of captions to save next
+    /// to the video, once it is downloaded.
+    pub fn download(
+        &self,
+        item: &DewYtItem,
+        source: DownloadSource,
+        captions: Option<(String, String)>,
+    ) {
+        self.imp().download(item, source, captions)
+    }
+    pub fn show_downloads(&self) {
+        self.imp().show_downloads()
+    }
+    /// Asks the user which playlist.
+    pub async fn add_to_playlist(&self, item: &DewYtItem) {
+        self.imp().playlists_page.imp().add_with_dialog(item).await
+    }
+    pub fn show_local_playlist(&self, id: &str) {
+        self.imp().show_local_playlist(id)
+    }
+    /// A playlist on the instance, not a local one.
+    pub async fn show_playlist(&self, id: &str) {
+        self.imp().show_playlist(id).await
+    }
+    pub fn rename_playlist(&self, id: &str, name: String) {
+        self.imp().playlists_page.imp().rename(id, name)
+    }
+    pub fn delete_playlist(&self, id: &str) {
+        self.imp().playlists_page.imp().delete(id)
+    }
+    pub fn remove_from_playlist(&self, id: &str, vid_id: &str) {
+        self.imp().playlists_page.imp().remove_video(id, vid_id)
+    }
+    pub fn add_to_library(&self, item: &DewYtItem, file: PathBuf) {
+        self.imp().library_page.imp().add(item, file)
+    }
+    /// Where the video was downloaded to, if it was.
+    pub fn downloaded_file(&self, id: &str) -> Option<PathBuf> {
+        self.imp().library_page.imp().file(id)
+    }
+    pub fn queue_is_empty(&self) -> bool {
+        self.imp().queue_page.imp().is_empty()
+    }
+    pub(crate) fn queue_changed(&self) {
+        self.imp().player_bar.update_next()
+    }
     pub(crate) fn spawn_blocking<F, R>(
         &self,
         task: F,
@@ -18,9 +18,11 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
+use std::cell::{Cell, RefCell};
+
 #[allow(unused_imports)]
 use adw::{prelude::*, subclass::prelude::*};
-use glib::g_warning;
+use glib::{g_warning, Variant};
 use gtk::{gio, glib};
 #[allow(unused_imports)]
 use gtk::{prelude::*, subclass::prelude::*};
@@ -37,8 +39,17 @@ mod imp {
     #[derive(Debug, Default, gtk::CompositeTemplate)]
     #[template(resource = "/null/daknig/DewDuct/yt_item_list.ui")]
     pub struct DewYtItemList {
+        #[template_child]
+        pub(super) scrolled_window: TemplateChild<gtk::ScrolledWindow>,
         #[template_child]
         pub(super) list_store: TemplateChild<gio::ListStore>,
+        /// Offered on every video row, see `set_row_menu`.
+        pub(super) row_menu: RefCell<Option<gio::MenuModel>>,
+        /// The queue itself does not offer adding to the queue.
+        pub(super) is_queue: Cell<bool>,
+        /// Activated with the id of a video that is clicked, instead of
+        /// `win.play`.
+        pub(super) activate_action: RefCell<Option<String>>,
     }
 
     #[glib::object_subclass]
@@ -52,6 +63,16 @@ mod imp {
             DewYtItem::ensure_type();
             klass.bind_template();
             klass.bind_template_callbacks();
+            klass.install_action(
+                "list.enqueue",
+                Some(glib::VariantTy::STRING),
+                Self::Type::enqueue,
+            );
+            klass.install_action_async(
+                "list.add-to-playlist",
+                Some(glib::VariantTy::STRING),
+                Self::Type::add_to_playlist,
+            );
         }
         // g_get_tmp_dir ###@@
         fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
@@ -74,21 +95,38 @@ mod imp {
             };
             let item: &DewYtItem = item.downcast_ref().unwrap();
             let id: String = item.id();
-            match item.kind() {
-                Video => list_view
+            let activate_action = list_view
+                .ancestor(super::DewYtItemList::static_type())
+                .and_downcast::<super::DewYtItemList>()
+                .and_then(|list| {
+                    list.imp().activate_action.borrow().clone()
+                });
+            match (item.kind(), activate_action) {
+                (Video, Some(action)) => list_view
+                    .activate_action(&action, Some(&id.to_variant()))
+                    .unwrap_or_else(|err| {
+                        g_warning!("DewYtItemList", "{}", err)
+                    }),
+                (Video, None) => list_view
                     .activate_action(
                         "win.play",
                         Some(&Some(id).to_variant()),
                     )
                     .expect("the action win.play does not exist"),
-                Channel => {
+                (Channel, _) => {
                     let window: crate::window::DewDuctWindow =
                         list_view.root().and_downcast().unwrap();
 
                     window.show_channel_yt_item(item).await;
                 }
+                (Playlist, _) => {
+                    let window: crate::window::DewDuctWindow =
+                        list_view.root().and_downcast().unwrap();
+
+                    window.show_playlist(&id).await;
+                }
                 // clicking on the header outside buttons- does nothing.
-                Header => {}
+                (Header, _) => {}
             }
         }
 
@@ -98,8 +136,37 @@ mod imp {
             list_item.set_child(Some(&row));
         }
 
-        #[template_callback(function)]
-        async fn bind_row(list_item: gtk::ListItem) {
+        /// The row menu, with the id of the item as the target of every
+        /// action.
+        fn row_menu_for(&self, id: &str) -> Option<gio::MenuModel> {
+            let menu = gio::Menu::new();
+            menu.append(
+                Some("Add to _Playlist…"),
+                Some(&gio::Action::print_detailed_name(
+                    "list.add-to-playlist",
+                    Some(&id.to_variant()),
+                )),
+            );
+            if !self.is_queue.get() {
+                menu.append(
+                    Some("Add to _Queue"),
+                    Some(&gio::Action::print_detailed_name(
+                        "list.enqueue",
+                        Some(&id.to_variant()),
+                    )),
+                );
+            }
+
+            let row_menu = self.row_menu.borrow();
+            let Some(row_menu) = row_menu.as_ref() else {
+                return Some(menu.upcast());
+            };
+            append_with_target(&menu, row_menu, id);
+            Some(menu.upcast())
+        }
+
+        #[template_callback]
+        async fn bind_row(&self, list_item: gtk::ListItem) {
             let item = list_item.item();
             let item: &DewYtItem = item
                 .and_downcast_ref()
@@ -121,6 +188,11 @@ mod imp {
                 let row: DewYtItemRow =
                     list_item.child().and_downcast().unwrap_or_default();
 
+                let menu = match item.kind() {
+                    DewYtItemKind::Video => self.row_menu_for(&item.id()),
+                    _ => None,
+                };
+                row.set_menu(menu.as_ref());
                 row.set_from_yt_item(item).await.unwrap_or_else(|err| {
                     glib::g_warning!(
                         "DewYtItemList",
@@ -142,6 +214,39 @@ mod imp {
     }
 }
 
+/// Copies the items of `model` into `menu`, with `id` as the target of
+/// every action. Sections are kept as sections.
+fn append_with_target(menu: &gio::Menu, model: &gio::MenuModel, id: &str) {
+    for i in 0..model.n_items() {
+        if let Some(section) = model.item_link(i, gio::MENU_LINK_SECTION) {
+            let targeted = gio::Menu::new();
+            append_with_target(&targeted, &section, id);
+            menu.append_section(None, &targeted);
+            continue;
+        }
+        let attribute = |name| {
+            model
+                .item_attribute_value(
+                    i,
+                    name,
+                    Some(glib::VariantTy::STRING),
+                )
+                .and_then(|value| value.get::<String>())
+        };
+        let (Some(label), Some(action)) =
+            (attribute("label"), attribute("action"))
+        else {
+            continue;
+        };
+        let item = gio::MenuItem::new(Some(&label), None);
+        item.set_action_and_target_value(
+            Some(&action),
+            Some(&id.to_variant()),
+        );
+        menu.append_item(&item);
+    }
+}
+
 glib::wrapper! {
     pub struct DewYtItemList(ObjectSubclass<imp::DewYtItemList>)
         @extends gtk::Widget, adw::Bin,
@@ -163,6 +268,64 @@ impl DewYtItemList {
             f(a, b)
         });
     }
+    /// Gives every video row a menu with these items. Their actions are
+    /// activated with the id of the video as the target, so they should
+    /// take a string parameter.
+    pub fn set_row_menu(&self, menu: &impl IsA<gio::MenuModel>) {
+        self.imp().row_menu.replace(Some(menu.clone().upcast()));
+    }
+    /// For the queue itself, which has no use for "Add to Queue".
+    pub fn set_is_queue(&self, is_queue: bool) {
+        self.imp().is_queue.set(is_queue);
+    }
+    /// What clicking a video does, for lists of videos that should not
+    /// just be played. The action takes the id as a string.
+    pub fn set_activate_action(&self, action: &str) {
+        self.imp().activate_action.replace(Some(action.to_owned()));
+    }
+    pub fn enqueue(&self, _: &str, param: Option<&Variant>) {
+        let Some(id) = param.and_then(|param| param.get::<String>()) else {
+            g_warning!("DewYtItemList", "list.enqueue needs an id");
+            return;
+        };
+        let Some(item) = self.get_vec().into_iter().find(|x| x.id() == id)
+        else {
+            return;
+        };
+        let window: crate::window::DewDuctWindow =
+            self.root().and_downcast().unwrap();
+        window.enqueue(&[item]);
+    }
+    pub async fn add_to_playlist(self, _: String, param: Option<Variant>) {
+        let Some(id) = param.and_then(|param| param.get::<String>()) else {
+            g_warning!("DewYtItemList", "list.add-to-playlist needs an id");
+            return;
+        };
+        let Some(item) = self.get_vec().into_iter().find(|x| x.id() == id)
+        else {
+            return;
+        };
+        let window: crate::window::DewDuctWindow =
+            self.root().and_downcast().unwrap();
+        window.add_to_playlist(&item).await;
+    }
+    /// Moves the item `by` places, up for negative numbers, as far as it
+    /// can go.
+    pub fn move_item_with_id(&self, id: &str, by: i32) {
+        let list_store = &self.imp().list_store;
+        let Some(from) =
+            self.get_vec().into_iter().position(|x| x.id() == id)
+        else {
+            return;
+        };
+        let last = list_store.n_items().saturating_sub(1) as i32;
+        let to = (from as i32 + by).clamp(0, last) as u32;
+        let Some(item) = list_store.item(from as u32) else {
+            return;
+        };
+        list_store.remove(from as u32);
+        list_store.insert(to, &item);
+    }
     pub fn del_item_with_id(&self, id: String) {
         let list_store = &self.imp().list_store;
         list_store.retain(|obj| {
@@ -177,6 +340,29 @@ impl DewYtItemList {
         list_store.splice(0, list_store.n_items(), &vec);
     }
 
+    /// After the items already there.
+    pub fn append(&self, vec: Vec<DewYtItem>) {
+        self.imp().list_store.extend_from_slice(&vec);
+    }
+
+    /// When the list is scrolled all the way down, for loading more.
+    pub fn connect_end_reached(
+        &self,
+        f: impl Fn(&Self) + 'static,
+    ) -> glib::signal::SignalHandlerId {
+        let list = self.downgrade();
+        self.imp().scrolled_window.connect_edge_reached(
+            move |_, position| {
+                if position != gtk::PositionType::Bottom {
+                    return;
+                }
+                if let Some(list) = list.upgrade() {
+                    f(&list);
+                }
+            },
+        )
+    }
+
     pub fn get_vec(&self) -> impl IntoIterator<Item = DewYtItem> + '_ {
         let list_store = &self.imp().list_store;
         list_store
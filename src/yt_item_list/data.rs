@@ -29,6 +29,7 @@ use std::cell::{Cell, Ref, RefCell};
 use std::rc::Rc;
 
 use invidious::channel::Channel;
+use serde::{Deserialize, Serialize};
 
 #[derive(Default, Debug, Copy, Clone, PartialEq, Eq, glib::Enum)]
 #[enum_type(name = "MyEnum")]
@@ -36,12 +37,13 @@ pub enum DewYtItemKind {
     #[default]
     Video,
     Channel,
+    Playlist,
     // header because of the limitation of ListView, you cant have it as a
     // separate widget on top of the list... sad.
     Header,
 }
 
-#[derive(PartialEq, Eq, Clone)]
+#[derive(PartialEq, Eq, Clone, Deserialize, Serialize)]
 pub struct Thumbnail {
     pub url: String,
     pub width: u32,
@@ -111,6 +113,12 @@ mod imp_data {
         pub description: RefCell<Option<String>>,
         #[property(get, set)]
         pub subscribers: Cell<f32>,
+        /// For playlists.
+        #[property(get, set)]
+        pub video_count: Cell<u32>,
+        /// Published after the user last checked, for the feed.
+        #[property(get, set)]
+        pub is_new: Cell<bool>,
     }
 
     #[glib::object_subclass]
@@ -191,7 +199,7 @@ impl From<SearchItem> for DewYtItem {
         match vid {
             SearchItem::Video(ref vid) => vid.into(),
             SearchItem::Channel(chan) => chan.into(),
-            _ => todo!(),
+            SearchItem::Playlist(playlist) => playlist.into(),
         }
     }
 }
@@ -222,6 +230,48 @@ impl From<CommonChannel> for DewYtItem {
     }
 }
 
+impl From<CommonPlaylist> for DewYtItem {
+    fn from(playlist: CommonPlaylist) -> Self {
+        let CommonPlaylist {
+            title,
+            id,
+            thumbnail,
+            author,
+            video_count,
+            videos,
+            ..
+        } = playlist;
+        let ret: Self = glib::Object::builder()
+            .property("author", author)
+            .property("id", id)
+            .property("title", title)
+            .property("video-count", video_count)
+            .build();
+        // the playlist thumbnail is sometimes missing, the first video's
+        // will do then.
+        let thumbnails: Vec<_> = if thumbnail.is_empty() {
+            videos
+                .into_iter()
+                .next()
+                .map(|vid| {
+                    vid.thumbnails.into_iter().map(|x| x.into()).collect()
+                })
+                .unwrap_or_default()
+        } else {
+            // that's `hqdefault.jpg`.
+            vec![Thumbnail {
+                url: normalize_thumbnail_url(thumbnail),
+                width: 480,
+                height: 360,
+            }]
+        };
+        ret.set_thumbnails(thumbnails);
+        ret.set_kind(DewYtItemKind::Playlist);
+
+        ret
+    }
+}
+
 impl From<Channel> for DewYtItem {
     fn from(chan: Channel) -> Self {
         let cc: CommonChannel = chan.into();
@@ -266,6 +316,79 @@ impl From<PopularItem> for DewYtItem {
     }
 }
 
+use invidious::hidden::PlaylistItem;
+impl From<PlaylistItem> for DewYtItem {
+    fn from(item: PlaylistItem) -> Self {
+        let PlaylistItem {
+            title,
+            id,
+            author,
+            thumbnails,
+            length,
+            ..
+        } = item;
+
+        let ret: Self = glib::Object::builder()
+            .property("author", author)
+            .property("id", id)
+            .property("length", length as u64)
+            .property("title", title)
+            .build();
+
+        let thumbnails: Vec<_> =
+            thumbnails.into_iter().map(|x| x.into()).collect();
+        ret.set_thumbnails(thumbnails);
+        ret.set_kind(DewYtItemKind::Video);
+
+        ret
+    }
+}
+
+/// The view count from text like "1,234 views" or "1.2M views".
+fn parse_views_text(text: &str) -> u64 {
+    let number = text.split_whitespace().next().unwrap_or_default();
+    let (number, multiplier) = match number.chars().last() {
+        Some('K') => (&number[..number.len() - 1], 1e3),
+        Some('M') => (&number[..number.len() - 1], 1e6),
+        Some('B') => (&number[..number.len() - 1], 1e9),
+        _ => (number, 1.),
+    };
+    let number: String = number.chars().filter(|c| *c != ',').collect();
+    number
+        .parse::<f64>()
+        .map(|number| (number * multiplier) as u64)
+        .unwrap_or(0)
+}
+
+use invidious::hidden::VideoShort;
+impl From<VideoShort> for DewYtItem {
+    fn from(vid: VideoShort) -> Self {
+        let VideoShort {
+            id,
+            title,
+            thumbnails,
+            author,
+            length,
+            views_text,
+        } = vid;
+
+        let ret: Self = glib::Object::builder()
+            .property("author", author)
+            .property("id", id)
+            .property("length", length as u64)
+            .property("title", title)
+            .property("views", parse_views_text(&views_text))
+            .build();
+
+        let thumbnails: Vec<_> =
+            thumbnails.into_iter().map(|x| x.into()).collect();
+        ret.set_thumbnails(thumbnails);
+        ret.set_kind(DewYtItemKind::Video);
+
+        ret
+    }
+}
+
 use invidious::video::Video;
 impl From<Video> for DewYtItem {
     fn from(vid: Video) -> Self {
@@ -310,7 +433,7 @@ impl From<Video> for DewYtItem {
     }
 }
 
-use invidious::{CommonChannel, CommonVideo};
+use invidious::{CommonChannel, CommonPlaylist, CommonVideo};
 impl From<&CommonVideo> for DewYtItem {
     fn from(vid: &CommonVideo) -> Self {
         let CommonVideo {
@@ -348,3 +471,86 @@ impl From<&CommonVideo> for DewYtItem {
         ret
     }
 }
+
+/// Everything about a video item, to keep it on disk and show it again
+/// without asking for it.
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DewYtItemSnapshot {
+    pub id: String,
+    pub title: String,
+    pub author: String,
+    pub author_thumbnails: Vec<Thumbnail>,
+    pub length: u64,
+    pub thumbnails: Vec<Thumbnail>,
+    pub views: u64,
+    pub published: u64,
+    pub sub_count_text: String,
+    pub live: bool,
+    pub likes: i32,
+    pub description: Option<String>,
+}
+
+impl From<&DewYtItem> for DewYtItemSnapshot {
+    fn from(item: &DewYtItem) -> Self {
+        Self {
+            id: item.id(),
+            title: item.title(),
+            author: item.author(),
+            author_thumbnails: item.author_thumbnails().clone(),
+            length: item.length(),
+            thumbnails: item.thumbnails().to_vec(),
+            views: item.views(),
+            published: item.published(),
+            sub_count_text: item.sub_count_text(),
+            live: item.live(),
+            likes: item.likes(),
+            description: item.description(),
+        }
+    }
+}
+
+impl From<DewYtItemSnapshot> for DewYtItem {
+    fn from(snapshot: DewYtItemSnapshot) -> Self {
+        let ret: Self = glib::Object::builder()
+            .property("author", snapshot.author)
+            .property("description", snapshot.description)
+            .property("id", snapshot.id)
+            .property("length", snapshot.length)
+            .property("likes", snapshot.likes)
+            .property("live", snapshot.live)
+            .property("published", snapshot.published)
+            .property("sub-count-text", snapshot.sub_count_text)
+            .property("title", snapshot.title)
+            .property("views", snapshot.views)
+            .build();
+
+        ret.set_author_thumbnails(snapshot.author_thumbnails);
+        ret.set_thumbnails(snapshot.thumbnails);
+        ret.set_kind(DewYtItemKind::Video);
+
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn views_text() {
+        assert_eq!(parse_views_text("1 view"), 1);
+        assert_eq!(parse_views_text("1,234 views"), 1234);
+        assert_eq!(parse_views_text("1.2K views"), 1200);
+        assert_eq!(parse_views_text("2.5M views"), 2_500_000);
+        assert_eq!(parse_views_text("3B views"), 3_000_000_000);
+    }
+
+    #[test]
+    fn views_text_unparsable() {
+        assert_eq!(parse_views_text("No views"), 0);
+        assert_eq!(parse_views_text(""), 0);
+        assert_eq!(parse_views_text("K views"), 0);
+        assert_eq!(parse_views_text("many views"), 0);
+    }
+}
@@ -25,6 +25,7 @@ use gtk::{gio, glib};
 use gtk::{prelude::*, subclass::prelude::*};
 
 use crate::channel_row::DewChannelRow;
+use crate::playlist_row::DewPlaylistRow;
 use crate::video_row::DewVideoRow;
 
 mod imp {
@@ -41,6 +42,8 @@ mod imp {
         pub(super) video_row: TemplateChild<DewVideoRow>,
         #[template_child]
         pub(super) channel_row: TemplateChild<DewChannelRow>,
+        #[template_child]
+        pub(super) playlist_row: TemplateChild<DewPlaylistRow>,
     }
 
     #[glib::object_subclass]
@@ -86,6 +89,16 @@ impl DewYtItemRow {
         self.imp().stack.set_visible_child(&chan);
         chan
     }
+    pub fn become_playlist(&self) -> DewPlaylistRow {
+        let playlist = self.imp().playlist_row.get();
+        self.imp().stack.set_visible_child(&playlist);
+        playlist
+    }
+
+    /// Only videos have a menu, for now.
+    pub fn set_menu(&self, menu: Option<&gio::MenuModel>) {
+        self.imp().video_row.set_menu(menu);
+    }
 
     pub async fn set_from_yt_item(
         &self,
@@ -94,17 +107,18 @@ impl DewYtItemRow {
         use DewYtItemKind::*;
         match item.kind() {
             Video => {
-                self.become_video()
-                    .set_from_params(
-                        item.author(),
-                        item.id(),
-                        item.length(),
-                        item.published(),
-                        item.thumbnails().iter(),
-                        item.title(),
-                        item.views(),
-                    )
-                    .await?;
+                let row = self.become_video();
+                row.set_new(item.is_new());
+                row.set_from_params(
+                    item.author(),
+                    item.id(),
+                    item.length(),
+                    item.published(),
+                    item.thumbnails().iter(),
+                    item.title(),
+                    item.views(),
+                )
+                .await?;
             }
             Channel => {
                 self.become_channel()
@@ -116,6 +130,17 @@ impl DewYtItemRow {
                     )
                     .await?;
             }
+            Playlist => {
+                self.become_playlist()
+                    .set_from_params(
+                        item.title(),
+                        item.author(),
+                        item.video_count(),
+                        &item.thumbnails(),
+                        item.id(),
+                    )
+                    .await?;
+            }
             Header => unreachable!(),
         }
         Ok(())